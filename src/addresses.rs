@@ -0,0 +1,96 @@
+use anyhow::Result;
+use crate::net::{resolve, Family};
+use crate::report::{AddressCheck, Issue};
+use crate::util::fingerprint_sha256;
+use crate::validate::{validate_chain, TrustStore};
+use openssl::x509::{X509Ref, X509};
+use std::net::SocketAddr;
+
+// `--all-addresses`: the same handshake against every A/AAAA record, to catch
+// one node serving a stale leaf or an incomplete chain.
+
+/// Every distinct address `host` resolves to, in resolver order, limited to
+/// `family` when set.
+pub fn resolve_all(host: &str, port: u16, family: Option<Family>) -> Result<Vec<SocketAddr>> {
+    resolve((host, port), host, family)
+}
+
+/// Fetch the served chain from each address with `fetch` and compare the
+/// leaves; the most common leaf is the reference.
+pub fn check_addresses(
+    addrs: &[SocketAddr],
+    trust: &TrustStore,
+    fetch: impl Fn(&str) -> Result<Vec<X509>>,
+) -> Vec<AddressCheck> {
+    let mut checks: Vec<AddressCheck> = addrs
+        .iter()
+        .map(|addr| {
+            let address = addr.to_string();
+            match fetch(&address) {
+                Ok(certs) => inspect(address, &certs, trust),
+                Err(e) => AddressCheck {
+                    address,
+                    chain_fingerprints: Vec::new(),
+                    verify_error: None,
+                    different_leaf: false,
+                    error: Some(format!("{:#}", e)),
+                },
+            }
+        })
+        .collect();
+
+    // Ties go to the address resolved first
+    let leaves: Vec<String> = checks.iter().filter_map(|c| c.chain_fingerprints.first().cloned()).collect();
+    let mut reference: Option<&String> = None;
+    let mut best = 0;
+    for fp in &leaves {
+        let count = leaves.iter().filter(|other| *other == fp).count();
+        if count > best {
+            (reference, best) = (Some(fp), count);
+        }
+    }
+    let reference = reference.cloned();
+    for check in &mut checks {
+        check.different_leaf = matches!((check.chain_fingerprints.first(), &reference), (Some(fp), Some(r)) if fp != r);
+    }
+    checks
+}
+
+/// Errors for addresses that failed, served another leaf or a chain that
+/// does not verify.
+pub fn address_issues(checks: &[AddressCheck]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for c in checks {
+        if let Some(err) = &c.error {
+            issues.push(Issue::error(format!("{}: {}", c.address, err)));
+            continue;
+        }
+        if c.different_leaf {
+            let fp = c.chain_fingerprints.first().map(String::as_str).unwrap_or_default();
+            issues.push(Issue::error(format!("{} serves a different leaf certificate ({})", c.address, fp)));
+        }
+        if let Some(err) = &c.verify_error {
+            issues.push(Issue::error(format!("{} serves a chain that does not verify: {}", c.address, err)));
+        }
+    }
+    issues
+}
+
+fn inspect(address: String, certs: &[X509], trust: &TrustStore) -> AddressCheck {
+    let seq: Vec<&X509Ref> = certs.iter().map(|c| c.as_ref()).collect();
+    let verify_error = match seq.split_first() {
+        Some((leaf, chain)) => match validate_chain(leaf, chain, trust) {
+            Ok(Ok(_)) => None,
+            Ok(Err(msg)) => Some(msg),
+            Err(e) => Some(format!("validation error: {}", e)),
+        },
+        None => Some("no certificate presented".to_string()),
+    };
+    AddressCheck {
+        address,
+        chain_fingerprints: seq.iter().filter_map(|c| fingerprint_sha256(c).ok()).collect(),
+        verify_error,
+        different_leaf: false,
+        error: None,
+    }
+}
@@ -1,5 +1,7 @@
-use clap::{ArgGroup, Args, Parser, Subcommand};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Top-level CLI with subcommands.
 #[derive(Parser, Debug)]
@@ -12,38 +14,321 @@ pub struct Cli {
 #[derive(Subcommand, Debug)]
 pub enum Command {
     /// Diagnose a live server or a PEM bundle
-    Diag(DiagArgs),
-    /// Scaffold a complete bundle from a leaf certificate file
+    Diag(Box<DiagArgs>),
+    /// Enumerate the protocol versions and cipher suites a server accepts
+    Scan(ScanArgs),
+    /// Check that a private key belongs to a certificate
+    Keymatch(KeymatchArgs),
+    /// Scaffold a complete bundle from a leaf certificate file, or repair an existing bundle
     Scaffold(ScaffoldArgs),
 }
 
 #[derive(Args, Debug)]
 #[command(group(ArgGroup::new("input").required(true).args(["server", "file"])))]
+#[command(group(ArgGroup::new("custom_ca").multiple(true).args(["ca_file", "ca_dir"])))]
 pub struct DiagArgs {
     /// Domain name or IP of the server to connect to
     #[arg(short = 's', long = "server")]
     pub server: Option<String>,
 
-    /// PEM bundle file (one or more concatenated certificates)
+    /// Certificate file: PEM bundle, DER certificate, PKCS#7 (.p7b/.p7c, PEM or DER) or PKCS#12 (.pfx/.p12)
     #[arg(short = 'f', long = "file")]
     pub file: Option<PathBuf>,
 
-    /// Port of the server (default: 443)
-    #[arg(short = 'p', long = "port", default_value_t = 443)]
-    pub port: u16,
+    /// Password of a PKCS#12 --file
+    #[arg(long = "password", requires = "file")]
+    pub password: Option<String>,
+
+    /// Private key (PEM or DER PKCS#8) to check against the leaf of --file
+    #[arg(long = "key", value_name = "FILE", requires = "file")]
+    pub key: Option<PathBuf>,
+
+    /// Passphrase of an encrypted --key
+    #[arg(long = "key-password", value_name = "PASSPHRASE", requires = "key")]
+    pub key_password: Option<String>,
+
+    /// Port of the server (default: 443, or the protocol's standard port with --starttls)
+    #[arg(short = 'p', long = "port")]
+    pub port: Option<u16>,
+
+    /// Connect to this address instead of resolving --server (e.g. one backend behind a load balancer)
+    #[arg(long = "connect", value_name = "IP[:PORT]", value_parser = parse_connect, requires = "server")]
+    pub connect: Option<ConnectTarget>,
+
+    /// Repeat the handshake against every address --server resolves to and compare the chains
+    #[arg(long = "all-addresses", conflicts_with = "connect", requires = "server")]
+    pub all_addresses: bool,
+
+    /// Server name to send in SNI and verify against (default: --server)
+    #[arg(long = "sni", requires = "server")]
+    pub sni: Option<String>,
+
+    /// Send no SNI extension, to see the server's default certificate
+    #[arg(long = "no-sni", conflicts_with = "sni", requires = "server")]
+    pub no_sni: bool,
+
+    /// Upgrade a plaintext connection with the protocol's STARTTLS dialogue before the handshake
+    #[arg(long = "starttls", value_enum, requires = "server")]
+    pub starttls: Option<StartTls>,
+
+    /// Seconds to wait for each TCP connection to be accepted
+    #[arg(long = "connect-timeout", value_name = "SECS", default_value = "10", value_parser = parse_seconds)]
+    pub connect_timeout: Duration,
+
+    /// Seconds to wait for each read or write of the STARTTLS dialogue and TLS handshake
+    #[arg(long = "handshake-timeout", value_name = "SECS", default_value = "10", value_parser = parse_seconds)]
+    pub handshake_timeout: Duration,
+
+    /// Retry this many times when the connection is refused or times out
+    #[arg(long = "retries", default_value_t = 0)]
+    pub retries: u32,
+
+    /// Only connect over IPv4
+    #[arg(short = '4', conflicts_with = "ipv6")]
+    pub ipv4: bool,
+
+    /// Only connect over IPv6
+    #[arg(short = '6')]
+    pub ipv6: bool,
+
+    /// Proxy to tunnel through: http://[user:pass@]host[:port] or socks5://... (default: HTTPS_PROXY/ALL_PROXY, minus NO_PROXY)
+    #[arg(long = "proxy", value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// Client certificate to present (mutual TLS): PEM certificate (plus chain) or PKCS#12 file
+    #[arg(long = "client-cert", value_name = "FILE", requires = "server")]
+    pub client_cert: Option<PathBuf>,
+
+    /// PEM private key of --client-cert when it is not in the same file
+    #[arg(long = "client-key", value_name = "FILE", requires = "client_cert")]
+    pub client_key: Option<PathBuf>,
+
+    /// Password of the PKCS#12 file or of an encrypted PEM key
+    #[arg(long = "client-password", value_name = "PASSWORD", requires = "client_cert")]
+    pub client_password: Option<String>,
 
     /// Disable certificate verification (like -verify 0). Useful for inspecting invalid chains.
     #[arg(long)]
     pub insecure: bool,
+
+    /// Check that the leaf certificate covers this name (SAN dNSName/iPAddress, RFC 6125 wildcards)
+    #[arg(long = "hostname")]
+    pub hostname: Option<String>,
+
+    /// PEM bundle of trust anchors to validate against (e.g. an internal root CA)
+    #[arg(long = "ca-file")]
+    pub ca_file: Option<PathBuf>,
+
+    /// Hashed directory of trust anchors (as created by `openssl rehash`)
+    #[arg(long = "ca-dir")]
+    pub ca_dir: Option<PathBuf>,
+
+    /// Do not trust the system store; only --ca-file/--ca-dir anchors are used
+    #[arg(long = "no-system-trust", requires = "custom_ca")]
+    pub no_system_trust: bool,
+
+    /// Flag certificates expiring within this many days as a warning
+    #[arg(long = "warn-days", default_value_t = 30)]
+    pub warn_days: i64,
+
+    /// Flag certificates expiring within this many days as an error
+    #[arg(long = "crit-days", default_value_t = 7)]
+    pub crit_days: i64,
+
+    /// Check the leaf's revocation status with its OCSP responder (AIA)
+    #[arg(long)]
+    pub ocsp: bool,
+
+    /// Check every certificate against the CRL from its distribution points
+    #[arg(long = "crl-check")]
+    pub crl_check: bool,
+
+    /// Local CRL file (PEM or DER) used instead of the distribution points; repeatable, implies --crl-check
+    #[arg(long = "crl")]
+    pub crl: Vec<PathBuf>,
+
+    /// Inspect the leaf's Certificate Transparency SCTs (embedded, TLS extension, stapled OCSP)
+    #[arg(long)]
+    pub sct: bool,
+
+    /// CT log list (v3 log_list.json) used to verify SCT signatures; implies --sct
+    #[arg(long = "ct-logs")]
+    pub ct_logs: Option<PathBuf>,
+
+    /// Show every decoded certificate extension (key identifiers, AIA, CRL, policies...)
+    #[arg(short = 'v', long = "verbose")]
+    pub verbose: bool,
+
+    /// Output format: human-readable text or a single JSON document
+    #[arg(long = "output", value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// `--connect` address; the port falls back to `--port`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConnectTarget {
+    pub ip: IpAddr,
+    pub port: Option<u16>,
+}
+
+// Accepts `10.0.0.5`, `10.0.0.5:8443`, `::1` and `[::1]:8443`.
+fn parse_connect(value: &str) -> Result<ConnectTarget, String> {
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Ok(ConnectTarget { ip: addr.ip(), port: Some(addr.port()) });
+    }
+    let ip = value.trim_start_matches('[').trim_end_matches(']');
+    ip.parse()
+        .map(|ip| ConnectTarget { ip, port: None })
+        .map_err(|_| format!("expected IP[:PORT], got {}", value))
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    match value.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(Duration::from_secs_f64(secs)),
+        _ => Err(format!("expected a positive number of seconds, got {}", value)),
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StartTls {
+    Smtp,
+    Imap,
+    Pop3,
+    Ftp,
+    Ldap,
+    Xmpp,
+    #[value(alias = "postgresql")]
+    Postgres,
+}
+
+#[derive(Args, Debug)]
+pub struct ScanArgs {
+    /// Domain name or IP of the server to scan
+    #[arg(short = 's', long = "server", required = true)]
+    pub server: String,
+
+    /// Port of the server (default: 443)
+    #[arg(short = 'p', long = "port", default_value_t = 443)]
+    pub port: u16,
+
+    /// Seconds to wait for each TCP connection to be accepted
+    #[arg(long = "connect-timeout", value_name = "SECS", default_value = "10", value_parser = parse_seconds)]
+    pub connect_timeout: Duration,
+
+    /// Seconds to wait for each read or write of a probe handshake
+    #[arg(long = "handshake-timeout", value_name = "SECS", default_value = "10", value_parser = parse_seconds)]
+    pub handshake_timeout: Duration,
+
+    /// Output format: human-readable text or a single JSON document
+    #[arg(long = "output", value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
 }
 
 #[derive(Args, Debug)]
+pub struct KeymatchArgs {
+    /// Certificate or bundle (PEM, DER, PKCS#7 or PKCS#12); the key is compared with its leaf
+    #[arg(short = 'c', long = "cert", required = true)]
+    pub cert: PathBuf,
+
+    /// Password of a PKCS#12 --cert
+    #[arg(long = "password")]
+    pub password: Option<String>,
+
+    /// Private key file (PEM or DER PKCS#8; RSA, EC, Ed25519 or Ed448)
+    #[arg(short = 'k', long = "key", required = true)]
+    pub key: PathBuf,
+
+    /// Passphrase of an encrypted --key
+    #[arg(long = "key-password", value_name = "PASSPHRASE")]
+    pub key_password: Option<String>,
+
+    /// Output format: human-readable text or a single JSON document
+    #[arg(long = "output", value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("source").required(true).args(["input", "repair"])))]
 pub struct ScaffoldArgs {
-    /// Input leaf certificate file (PEM or DER)
-    #[arg(short = 'i', long = "input", required = true)]
-    pub input: PathBuf,
+    /// Input leaf certificate file (PEM, DER, PKCS#7 or PKCS#12 without password)
+    #[arg(short = 'i', long = "input")]
+    pub input: Option<PathBuf>,
+
+    /// Existing bundle to fix: duplicates and unrelated certificates dropped, reordered leaf to root, completed
+    #[arg(long = "repair", value_name = "BUNDLE")]
+    pub repair: Option<PathBuf>,
+
+    /// Output bundle destination (see --format); will be created/overwritten
+    #[arg(short = 'o', long = "output", required_unless_present = "in_place")]
+    pub output: Option<PathBuf>,
+
+    /// Rewrite the --repair bundle itself (--format must match it), keeping the original as <BUNDLE>.bak
+    #[arg(long = "in-place", conflicts_with_all = ["input", "output", "split"])]
+    pub in_place: bool,
+
+    /// Proxy for AIA fetches: http://[user:pass@]host[:port] or socks5://... (default: HTTPS_PROXY/ALL_PROXY, minus NO_PROXY)
+    #[arg(long = "proxy", value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// Directory of PEM/DER/PKCS#7 issuer certificates, consulted before the cache and AIA
+    #[arg(long = "intermediates-dir", value_name = "DIR")]
+    pub intermediates_dir: Option<PathBuf>,
+
+    /// Never go online: issuers only come from local files, the cache and the trust store
+    #[arg(long = "offline")]
+    pub offline: bool,
+
+    /// PEM or PKCS#7 bundle of intermediates (e.g. the CCADB/Mozilla list), used when AIA yields no issuer
+    #[arg(long = "intermediates-file", value_name = "FILE")]
+    pub intermediates_file: Option<PathBuf>,
+
+    /// Issuer cache location (default: $XDG_CACHE_HOME/tls-doctor/issuers or ~/.cache/tls-doctor/issuers)
+    #[arg(long = "cache-dir", value_name = "DIR", conflicts_with = "no_cache")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Neither read nor write the issuer cache
+    #[arg(long = "no-cache")]
+    pub no_cache: bool,
+
+    /// Leave the self-signed root out of the bundle
+    #[arg(long = "exclude-root", conflicts_with = "root_from_trust_store")]
+    pub exclude_root: bool,
+
+    /// End the bundle with the system trust anchor the chain verifies to
+    #[arg(long = "root-from-trust-store")]
+    pub root_from_trust_store: bool,
+
+    /// Write the leaf to --output and the rest of the chain to <name>.chain.<ext> beside it
+    #[arg(long = "split")]
+    pub split: bool,
+
+    /// Bundle format; DER writes one certificate per file (<name>.1.der, ... for the issuers)
+    #[arg(long = "format", value_enum, default_value_t = BundleFormat::Pem)]
+    pub format: BundleFormat,
+
+    /// Private key of the leaf, appended to PEM output (HAProxy style) or stored in the PKCS#12 file
+    #[arg(long = "key", value_name = "FILE")]
+    pub key: Option<PathBuf>,
+
+    /// Passphrase of an encrypted --key
+    #[arg(long = "key-password", value_name = "PASSPHRASE", requires = "key")]
+    pub key_password: Option<String>,
+
+    /// Password protecting --format p12 output (default: empty)
+    #[arg(long = "p12-password", value_name = "PASSWORD")]
+    pub p12_password: Option<String>,
+}
 
-    /// Output bundle destination (PEM); will be created/overwritten
-    #[arg(short = 'o', long = "output", required = true)]
-    pub output: PathBuf,
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BundleFormat {
+    Pem,
+    Der,
+    P7b,
+    P12,
 }
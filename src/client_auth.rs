@@ -0,0 +1,195 @@
+use anyhow::{bail, Context, Result};
+use crate::exit::Failure;
+use crate::ffi;
+use crate::keymatch::parse_private_key;
+use crate::report::{ClientAuth, Issue};
+use crate::util::format_name_human;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{self, SslContextBuilder, SslRef, SslStream};
+use openssl::x509::X509;
+use std::fs;
+use std::net::TcpStream;
+use std::path::Path;
+use std::time::Duration;
+
+// Mutual TLS: the certificate `diag` presents with `--client-cert`, and what
+// the server's CertificateRequest asked for.
+
+/// How long a TLS 1.3 server is given to reject our certificate.
+const VERDICT_GRACE: Duration = Duration::from_millis(500);
+
+/// Client certificate, its chain and key, as loaded from `--client-cert`.
+#[derive(Debug)]
+pub struct ClientIdentity {
+    pub cert: X509,
+    pub chain: Vec<X509>,
+    key: PKey<Private>,
+}
+
+impl ClientIdentity {
+    /// Load a PKCS#12 file, or a PEM certificate (plus chain) whose key is in
+    /// `key_path` or the same file. `password` opens the PKCS#12 file or an
+    /// encrypted PEM key.
+    pub fn load(cert_path: &Path, key_path: Option<&Path>, password: Option<&str>) -> Result<ClientIdentity> {
+        let data = fs::read(cert_path)
+            .with_context(|| Failure::Input(format!("failed to read client certificate {}", cert_path.display())))?;
+        let identity = match Pkcs12::from_der(&data) {
+            Ok(p12) => from_pkcs12(p12, password)
+                .with_context(|| Failure::Input(format!("failed to open PKCS#12 file {}", cert_path.display())))?,
+            Err(_) => from_pem(&data, cert_path, key_path, password)?,
+        };
+        if !identity.cert.public_key()?.public_eq(&identity.key) {
+            return Err(Failure::Input("client key does not match the client certificate".to_string()).into());
+        }
+        Ok(identity)
+    }
+
+    /// Present this identity when the server asks for a certificate.
+    pub fn configure(&self, builder: &mut SslContextBuilder) -> Result<()> {
+        builder.set_certificate(&self.cert)?;
+        builder.set_private_key(&self.key)?;
+        for cert in &self.chain {
+            builder.add_extra_chain_cert(cert.clone())?;
+        }
+        Ok(())
+    }
+}
+
+fn from_pkcs12(p12: Pkcs12, password: Option<&str>) -> Result<ClientIdentity> {
+    let parsed = p12.parse2(password.unwrap_or_default()).context("wrong password or corrupt file")?;
+    let (Some(cert), Some(key)) = (parsed.cert, parsed.pkey) else {
+        bail!("no certificate and private key inside");
+    };
+    let chain = parsed.ca.map(|stack| stack.into_iter().collect()).unwrap_or_default();
+    Ok(ClientIdentity { cert, chain, key })
+}
+
+fn from_pem(data: &[u8], cert_path: &Path, key_path: Option<&Path>, password: Option<&str>) -> Result<ClientIdentity> {
+    let mut certs = X509::stack_from_pem(data)
+        .ok()
+        .filter(|certs| !certs.is_empty())
+        .with_context(|| Failure::Input(format!("{} is neither a PEM certificate nor PKCS#12", cert_path.display())))?
+        .into_iter();
+    let cert = certs.next().expect("stack is not empty");
+    let (key_data, key_file) = match key_path {
+        Some(path) => (
+            fs::read(path).with_context(|| Failure::Input(format!("failed to read client key {}", path.display())))?,
+            path,
+        ),
+        None => (data.to_vec(), cert_path),
+    };
+    let key = parse_private_key(&key_data, password).with_context(|| Failure::Input(format!("failed to load a private key from {}", key_file.display())))?;
+    Ok(ClientIdentity { cert, chain: certs.collect(), key })
+}
+
+/// What the server's CertificateRequest contained (read after the handshake,
+/// or after it failed) and the certificate presented in reply.
+pub fn certificate_request(ssl: &SslRef, identity: Option<&ClientIdentity>) -> ClientAuth {
+    let cas = peer_ca_names(ssl);
+    let signature_algorithms = peer_sigalgs(ssl);
+    ClientAuth {
+        // TLS 1.2 and 1.3 requests always carry signature algorithms
+        requested: !signature_algorithms.is_empty() || !cas.is_empty(),
+        acceptable_cas: cas.iter().map(|(name, _)| name.clone()).collect(),
+        signature_algorithms,
+        presented: identity.map(|id| format_name_human(id.cert.subject_name().entries())),
+        presented_accepted: identity.map(|id| {
+            cas.is_empty()
+                || std::iter::once(&id.cert).chain(&id.chain).any(|c| {
+                    let issuer = c.issuer_name().to_der().unwrap_or_default();
+                    cas.iter().any(|(_, der)| *der == issuer)
+                })
+        }),
+    }
+}
+
+/// TLS 1.3 servers judge the client certificate after our Finished, so a
+/// rejection only arrives as an alert on the first read. Wait briefly for one.
+pub fn await_verdict(stream: &mut SslStream<TcpStream>) -> Result<(), ssl::Error> {
+    let previous = stream.get_ref().read_timeout().ok().flatten();
+    let _ = stream.get_ref().set_read_timeout(Some(VERDICT_GRACE));
+    let result = match stream.ssl_read(&mut [0u8; 1]) {
+        Err(e) if e.ssl_error().is_some() => Err(e),
+        // Data, close_notify or nothing within the grace period
+        _ => Ok(()),
+    };
+    let _ = stream.get_ref().set_read_timeout(previous);
+    result
+}
+
+/// Warnings for a presented certificate the server did not ask for or whose
+/// issuer it does not list, and for a request left unanswered.
+pub fn client_auth_issues(auth: &ClientAuth) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    match (&auth.presented, auth.requested) {
+        (Some(_), false) => issues.push(Issue::warning("client certificate was not requested by the server")),
+        (None, true) => {
+            issues.push(Issue::warning("server requested a client certificate but none was presented (see --client-cert)"))
+        }
+        _ => {}
+    }
+    if auth.requested && auth.presented_accepted == Some(false) {
+        issues.push(Issue::warning("client certificate is not issued by any CA the server lists as acceptable"));
+    }
+    issues
+}
+
+/// One-line summary of a CertificateRequest, added to a failed handshake.
+pub fn describe_request(auth: &ClientAuth) -> String {
+    let cas = match auth.acceptable_cas.is_empty() {
+        true => "any".to_string(),
+        false => auth.acceptable_cas.join("; "),
+    };
+    format!(
+        "server requested a client certificate (acceptable CAs: {}; signature algorithms: {})",
+        cas,
+        auth.signature_algorithms.join(", ")
+    )
+}
+
+// Acceptable CA names with their DER, in the server's order.
+fn peer_ca_names(ssl: &SslRef) -> Vec<(String, Vec<u8>)> {
+    ffi::client_ca_names(ssl)
+        .into_iter()
+        .map(|name| (format_name_human(name.entries()), name.to_der().unwrap_or_default()))
+        .collect()
+}
+
+// Signature schemes of the request, by TLS name.
+fn peer_sigalgs(ssl: &SslRef) -> Vec<String> {
+    ffi::peer_sigalgs(ssl)
+        .into_iter()
+        .map(|code| scheme_name(code).map_or_else(|| format!("{:#06x}", code), str::to_string))
+        .collect()
+}
+
+// RFC 8446 section 4.2.3 SignatureScheme names, plus the TLS 1.2 legacy pairs.
+fn scheme_name(code: u16) -> Option<&'static str> {
+    Some(match code {
+        0x0201 => "rsa_pkcs1_sha1",
+        0x0203 => "ecdsa_sha1",
+        0x0301 => "rsa_pkcs1_sha224",
+        0x0303 => "ecdsa_sha224",
+        0x0401 => "rsa_pkcs1_sha256",
+        0x0501 => "rsa_pkcs1_sha384",
+        0x0601 => "rsa_pkcs1_sha512",
+        0x0403 => "ecdsa_secp256r1_sha256",
+        0x0503 => "ecdsa_secp384r1_sha384",
+        0x0603 => "ecdsa_secp521r1_sha512",
+        0x0804 => "rsa_pss_rsae_sha256",
+        0x0805 => "rsa_pss_rsae_sha384",
+        0x0806 => "rsa_pss_rsae_sha512",
+        0x0807 => "ed25519",
+        0x0808 => "ed448",
+        0x0809 => "rsa_pss_pss_sha256",
+        0x080a => "rsa_pss_pss_sha384",
+        0x080b => "rsa_pss_pss_sha512",
+        0x081a => "ecdsa_brainpoolP256r1tls13_sha256",
+        0x081b => "ecdsa_brainpoolP384r1tls13_sha384",
+        0x081c => "ecdsa_brainpoolP512r1tls13_sha512",
+        0x0202 => "dsa_sha1",
+        0x0402 => "dsa_sha256",
+        _ => return None,
+    })
+}
@@ -0,0 +1,202 @@
+use anyhow::{bail, Context, Result};
+use crate::exit::Failure;
+use crate::extensions::crl_distribution_urls;
+use crate::ocsp::revocation_reason_name;
+use crate::report::{CrlCheck, Issue, RevocationStatus};
+use crate::util::{cn_label, format_asn1_time};
+use openssl::asn1::Asn1Time;
+use openssl::x509::{CrlStatus, ReasonCode, X509Crl, X509Ref};
+use reqwest::blocking::Client;
+use std::path::Path;
+
+// CRL revocation checking (RFC 5280 section 6.3) for every certificate of an
+// ordered chain, from local files or the CRL distribution points.
+
+/// A CRL together with where it was loaded from.
+pub struct LoadedCrl {
+    pub source: String,
+    pub crl: X509Crl,
+}
+
+/// Load every CRL from `--crl` files (PEM, possibly concatenated, or DER).
+pub fn load_crl_files(paths: &[impl AsRef<Path>]) -> Result<Vec<LoadedCrl>> {
+    let mut out = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .with_context(|| Failure::Input(format!("failed to read CRL file {}", path.display())))?;
+        let crls = parse_crls(&data)
+            .with_context(|| Failure::Input(format!("failed to parse CRL from {}", path.display())))?;
+        out.extend(crls.into_iter().map(|crl| LoadedCrl { source: path.display().to_string(), crl }));
+    }
+    Ok(out)
+}
+
+fn parse_crls(data: &[u8]) -> Result<Vec<X509Crl>> {
+    const BEGIN: &str = "-----BEGIN X509 CRL-----";
+    let text = String::from_utf8_lossy(data);
+    if !text.contains(BEGIN) {
+        return Ok(vec![X509Crl::from_der(data)?]);
+    }
+    text.match_indices(BEGIN)
+        .map(|(start, _)| Ok(X509Crl::from_pem(text[start..].as_bytes())?))
+        .collect()
+}
+
+/// Check every certificate in `seq` that has its issuer in the chain. Local
+/// CRLs issued by that issuer take priority; otherwise the distribution
+/// points are fetched with `client` (skipped when `None`, as it is whenever
+/// local CRLs are given).
+pub fn check_crls(client: Option<&Client>, seq: &[&X509Ref], local: &[LoadedCrl]) -> Vec<CrlCheck> {
+    let mut out = Vec::new();
+    for (i, pair) in seq.windows(2).enumerate() {
+        let (cert, issuer) = (pair[0], pair[1]);
+        let issuer_name = issuer.subject_name().to_der().unwrap_or_default();
+        let mut check = unchecked(i + 1, cert);
+
+        // Several CRLs can name the same issuer (a re-keyed CA, an old copy):
+        // the first one signed by this issuer wins
+        let mut local_hits = local
+            .iter()
+            .filter(|l| l.crl.issuer_name().to_der().map(|d| d == issuer_name).unwrap_or(false))
+            .peekable();
+        if local_hits.peek().is_some() {
+            for l in local_hits {
+                check.source = Some(l.source.clone());
+                check.error = None;
+                evaluate(&mut check, &l.crl, cert, issuer);
+                if check.error.is_none() {
+                    break;
+                }
+            }
+            out.push(check);
+            continue;
+        }
+
+        let urls = crl_distribution_urls(cert);
+        if urls.is_empty() {
+            check.error = Some("no CRL distribution point and no matching --crl file".to_string());
+            out.push(check);
+            continue;
+        }
+        let Some(client) = client else {
+            check.error = Some("no matching --crl file".to_string());
+            out.push(check);
+            continue;
+        };
+        let mut errors = Vec::new();
+        for url in urls {
+            match fetch_crl(client, &url) {
+                Ok(crl) => {
+                    check.source = Some(url);
+                    evaluate(&mut check, &crl, cert, issuer);
+                    errors.clear();
+                    break;
+                }
+                Err(e) => errors.push(format!("{:#}", e)),
+            }
+        }
+        if !errors.is_empty() {
+            check.unreachable = true;
+            check.error = Some(errors.join("; "));
+        }
+        out.push(check);
+    }
+    out
+}
+
+/// Summary issues: revoked certificates, stale or unverifiable CRLs and
+/// distribution points that could not be fetched.
+pub fn crl_issues(checks: &[CrlCheck]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for c in checks {
+        match c.status {
+            RevocationStatus::Revoked => issues.push(Issue::error(format!(
+                "{} is revoked (CRL): {}{}",
+                c.subject,
+                c.reason.as_deref().unwrap_or("no reason given"),
+                c.revocation_date.as_ref().map(|d| format!(" since {}", d)).unwrap_or_default()
+            ))),
+            RevocationStatus::Error if c.unreachable => issues.push(Issue::warning(format!(
+                "CRL distribution point unreachable for {}: {}",
+                c.subject,
+                c.error.as_deref().unwrap_or("unknown error")
+            ))),
+            RevocationStatus::Error => issues.push(Issue::warning(format!(
+                "CRL check failed for {}: {}",
+                c.subject,
+                c.error.as_deref().unwrap_or("unknown error")
+            ))),
+            RevocationStatus::Good | RevocationStatus::Unknown => {}
+        }
+        if c.stale {
+            issues.push(Issue::warning(format!(
+                "CRL for {} is stale (next update {})",
+                c.subject,
+                c.next_update.as_deref().unwrap_or("-")
+            )));
+        }
+    }
+    issues
+}
+
+fn evaluate(check: &mut CrlCheck, crl: &X509Crl, cert: &X509Ref, issuer: &X509Ref) {
+    let verified = issuer.public_key().and_then(|k| crl.verify(&k)).unwrap_or(false);
+    if !verified {
+        check.error = Some("CRL signature does not verify against the issuer".to_string());
+        return;
+    }
+    check.this_update = Some(format_asn1_time(crl.last_update()));
+    check.next_update = crl.next_update().map(format_asn1_time);
+    let now = Asn1Time::days_from_now(0).ok();
+    check.stale = match (crl.next_update(), now.as_deref()) {
+        (Some(next), Some(now)) => next < now,
+        _ => false,
+    };
+    check.status = match crl.get_by_serial(cert.serial_number()) {
+        CrlStatus::Revoked(entry) => {
+            check.revocation_date = Some(format_asn1_time(entry.revocation_date()));
+            check.reason = entry
+                .extension::<ReasonCode>()
+                .ok()
+                .flatten()
+                .and_then(|(_, code)| code.get_i64().ok())
+                .and_then(|code| revocation_reason_name(code as i32))
+                .map(str::to_string);
+            RevocationStatus::Revoked
+        }
+        CrlStatus::NotRevoked | CrlStatus::RemoveFromCrl(_) => RevocationStatus::Good,
+    };
+}
+
+fn fetch_crl(client: &Client, url: &str) -> Result<X509Crl> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        bail!("unsupported distribution point {}", url);
+    }
+    let resp = client.get(url).send().with_context(|| format!("GET {} failed", url))?;
+    if !resp.status().is_success() {
+        bail!("{}: HTTP {}", url, resp.status());
+    }
+    let bytes = resp.bytes()?.to_vec();
+    parse_crls(&bytes)?
+        .into_iter()
+        .next()
+        .with_context(|| format!("no CRL in response from {}", url))
+}
+
+// Blank result for the certificate at chain position `index`.
+fn unchecked(index: usize, cert: &X509Ref) -> CrlCheck {
+    CrlCheck {
+        index,
+        subject: cn_label(cert),
+        source: None,
+        status: RevocationStatus::Error,
+        reason: None,
+        revocation_date: None,
+        this_update: None,
+        next_update: None,
+        stale: false,
+        unreachable: false,
+        error: None,
+    }
+}
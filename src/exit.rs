@@ -0,0 +1,65 @@
+use crate::report::Status;
+use std::fmt;
+use std::process::ExitCode;
+
+// Process exit codes so the tool can gate deployments. 1 is left to
+// unexpected errors and 2 to clap usage errors.
+
+pub const VALID: u8 = 0;
+pub const UNEXPECTED: u8 = 1;
+pub const WARNINGS: u8 = 3;
+pub const INVALID: u8 = 4;
+pub const CONNECTION: u8 = 5;
+pub const INPUT: u8 = 6;
+
+/// Failure category attached as anyhow context so `main` can pick an exit code.
+#[derive(Debug)]
+pub enum Failure {
+    /// TCP connect or TLS handshake did not complete.
+    Connection(String),
+    /// Server name did not resolve, or not to an address of the requested family.
+    Dns(String),
+    /// TCP connection refused.
+    Refused(String),
+    /// TCP connect, STARTTLS dialogue or TLS handshake timed out.
+    Timeout(String),
+    /// Server aborted the handshake with a TLS alert.
+    TlsAlert(String),
+    /// Input file could not be read or parsed.
+    Input(String),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Connection(msg)
+            | Failure::Dns(msg)
+            | Failure::Refused(msg)
+            | Failure::Timeout(msg)
+            | Failure::TlsAlert(msg)
+            | Failure::Input(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+pub fn for_status(status: Status) -> ExitCode {
+    ExitCode::from(match status {
+        Status::Valid => VALID,
+        Status::Warnings => WARNINGS,
+        Status::Invalid => INVALID,
+    })
+}
+
+pub fn for_error(err: &anyhow::Error) -> ExitCode {
+    // anyhow resolves context types through nested contexts as well
+    let code = match err.downcast_ref::<Failure>() {
+        Some(
+            Failure::Connection(_) | Failure::Dns(_) | Failure::Refused(_) | Failure::Timeout(_) | Failure::TlsAlert(_),
+        ) => CONNECTION,
+        Some(Failure::Input(_)) => INPUT,
+        None => UNEXPECTED,
+    };
+    ExitCode::from(code)
+}
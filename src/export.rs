@@ -0,0 +1,143 @@
+use anyhow::{bail, Context, Result};
+use crate::cli::BundleFormat;
+use crate::exit::Failure;
+use crate::util::{der_encode, subject_cn};
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::{PKey, Private};
+use openssl::stack::Stack;
+use openssl::x509::X509;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Writing a scaffolded chain in the shape its consumer expects: one PEM
+// file, leaf and chain apart, DER, PKCS#7 or PKCS#12.
+
+/// How `scaffold` lays out its output.
+#[derive(Debug)]
+pub struct ExportOptions {
+    pub format: BundleFormat,
+    /// Leaf in the output file, the rest of the chain in a `.chain` sibling.
+    pub split: bool,
+    /// Key of the leaf: appended to PEM output, or stored in PKCS#12.
+    pub key: Option<PKey<Private>>,
+    /// PKCS#12 password (empty if not given).
+    pub p12_password: String,
+}
+
+/// Write `chain` (leaf first) to `output` and, depending on the layout,
+/// sibling files. Returns every file written with its certificate count.
+pub fn write_bundle(output: &Path, chain: &[X509], opts: &ExportOptions) -> Result<Vec<(PathBuf, usize)>> {
+    if opts.split && opts.format == BundleFormat::P12 {
+        return Err(Failure::Input("--split does not apply to --format p12".to_string()).into());
+    }
+    if opts.key.is_some() && !matches!(opts.format, BundleFormat::Pem | BundleFormat::P12) {
+        return Err(Failure::Input("--key is only used with --format pem or p12".to_string()).into());
+    }
+    let Some((leaf, rest)) = chain.split_first() else {
+        bail!("nothing to write");
+    };
+
+    // DER holds one certificate per file: the leaf, then numbered issuers
+    let files: Vec<(PathBuf, &[X509])> = match (opts.format, opts.split) {
+        (BundleFormat::Der, _) => (0..chain.len())
+            .map(|i| match i {
+                0 => (output.to_path_buf(), &chain[..1]),
+                i => (sibling(output, &i.to_string(), opts.format), &chain[i..=i]),
+            })
+            .collect(),
+        (_, true) if rest.is_empty() => vec![(output.to_path_buf(), &chain[..1])],
+        (_, true) => vec![(output.to_path_buf(), &chain[..1]), (sibling(output, "chain", opts.format), rest)],
+        (_, false) => vec![(output.to_path_buf(), chain)],
+    };
+
+    let mut written = Vec::new();
+    for (path, certs) in files {
+        let with_key = opts.key.as_ref().filter(|_| certs[0].to_der().ok() == leaf.to_der().ok());
+        let data = match opts.format {
+            BundleFormat::Pem => pem(certs, with_key)?,
+            BundleFormat::Der => certs[0].to_der()?,
+            BundleFormat::P7b => p7b(certs)?,
+            BundleFormat::P12 => p12(certs, with_key, &opts.p12_password)?.to_der()?,
+        };
+        fs::write(&path, &data).with_context(|| format!("failed to write bundle to {}", path.display()))?;
+        written.push((path, certs.len()));
+    }
+    Ok(written)
+}
+
+fn pem(certs: &[X509], key: Option<&PKey<Private>>) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for cert in certs {
+        out.extend_from_slice(&cert.to_pem()?);
+    }
+    // Unencrypted, as HAProxy's combined `crt` file expects
+    if let Some(key) = key {
+        out.extend_from_slice(&key.private_key_to_pem_pkcs8()?);
+    }
+    Ok(out)
+}
+
+// Degenerate SignedData (RFC 2315 section 9.1 with no content and no
+// signers), as in a .p7b file: only the certificates are meaningful.
+fn p7b(certs: &[X509]) -> Result<Vec<u8>> {
+    // 1.2.840.113549.1.7.2 and 1.2.840.113549.1.7.1
+    const SIGNED_DATA: &[u8] = &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+    const DATA: &[u8] = &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01];
+    let mut ders = Vec::new();
+    for cert in certs {
+        ders.extend(cert.to_der()?);
+    }
+    let signed_data = [
+        der_encode(0x02, &[1]),  // version
+        der_encode(0x31, &[]),   // digestAlgorithms
+        der_encode(0x30, DATA),  // contentInfo, no content
+        der_encode(0xa0, &ders), // [0] IMPLICIT certificates
+        der_encode(0x31, &[]),   // signerInfos
+    ]
+    .concat();
+    let content = der_encode(0xa0, &der_encode(0x30, &signed_data));
+    Ok(der_encode(0x30, &[SIGNED_DATA, &content].concat()))
+}
+
+// The leaf and key as the key entry when a key is given, else every
+// certificate as a CA entry (a trust bundle).
+fn p12(certs: &[X509], key: Option<&PKey<Private>>, password: &str) -> Result<Pkcs12> {
+    let mut builder = Pkcs12::builder();
+    let mut ca = Stack::new()?;
+    match key {
+        Some(key) => {
+            builder.pkey(key).cert(&certs[0]);
+            if let Some(name) = subject_cn(&certs[0]) {
+                builder.name(&name);
+            }
+            for cert in &certs[1..] {
+                ca.push(cert.clone())?;
+            }
+        }
+        None => {
+            for cert in certs {
+                ca.push(cert.clone())?;
+            }
+        }
+    }
+    if !ca.is_empty() {
+        builder.ca(ca);
+    }
+    Ok(builder.build2(password)?)
+}
+
+// `bundle.pem` -> `bundle.chain.pem`, `leaf.der` -> `leaf.1.der`.
+fn sibling(output: &Path, tag: &str, format: BundleFormat) -> PathBuf {
+    let ext = output.extension().map_or_else(|| extension(format).to_string(), |e| e.to_string_lossy().into_owned());
+    let stem = output.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    output.with_file_name(format!("{}.{}.{}", stem, tag, ext))
+}
+
+fn extension(format: BundleFormat) -> &'static str {
+    match format {
+        BundleFormat::Pem => "pem",
+        BundleFormat::Der => "der",
+        BundleFormat::P7b => "p7b",
+        BundleFormat::P12 => "p12",
+    }
+}
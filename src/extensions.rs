@@ -0,0 +1,189 @@
+use crate::report::{AccessDescription, BasicConstraintsInfo, Extensions, OtherExtension};
+use crate::util::hex_colon;
+use openssl::x509::X509Ref;
+use std::net::IpAddr;
+use x509_parser::der_parser::der::parse_der_octetstring;
+use x509_parser::objects::{oid2sn, oid_registry};
+use x509_parser::prelude::*;
+
+// X.509v3 extension decoding on top of x509-parser (openssl only exposes a few).
+
+/// Decode the extensions shown in the certificate view. Unparseable
+/// certificates yield an empty set rather than an error.
+pub fn parse_extensions(cert: &X509Ref) -> Extensions {
+    let mut out = Extensions::default();
+    let der = match cert.to_der() { Ok(d) => d, Err(_) => return out };
+    let Ok((_, parsed)) = X509Certificate::from_der(&der) else { return out };
+
+    for ext in parsed.extensions() {
+        match ext.parsed_extension() {
+            ParsedExtension::SubjectAlternativeName(san) => {
+                out.subject_alt_names = san.general_names.iter().map(general_name_string).collect();
+            }
+            ParsedExtension::KeyUsage(ku) => {
+                out.key_usage = ku.to_string().split(", ").filter(|s| !s.is_empty()).map(str::to_string).collect();
+            }
+            ParsedExtension::ExtendedKeyUsage(eku) => {
+                let known = [
+                    (eku.any, "Any"),
+                    (eku.server_auth, "TLS Web Server Authentication"),
+                    (eku.client_auth, "TLS Web Client Authentication"),
+                    (eku.code_signing, "Code Signing"),
+                    (eku.email_protection, "E-mail Protection"),
+                    (eku.time_stamping, "Time Stamping"),
+                    (eku.ocsp_signing, "OCSP Signing"),
+                ];
+                out.extended_key_usage = known.iter().filter(|(on, _)| *on).map(|(_, l)| l.to_string()).collect();
+                out.extended_key_usage.extend(eku.other.iter().map(|o| o.to_id_string()));
+            }
+            ParsedExtension::BasicConstraints(bc) => {
+                out.basic_constraints = Some(BasicConstraintsInfo { ca: bc.ca, path_len: bc.path_len_constraint });
+            }
+            ParsedExtension::SubjectKeyIdentifier(ski) => {
+                out.subject_key_id = Some(hex_colon(ski.0));
+            }
+            ParsedExtension::AuthorityKeyIdentifier(aki) => {
+                out.authority_key_id = aki.key_identifier.as_ref().map(|k| hex_colon(k.0));
+            }
+            ParsedExtension::AuthorityInfoAccess(aia) => {
+                out.authority_info_access = aia
+                    .accessdescs
+                    .iter()
+                    .map(|ad| AccessDescription {
+                        method: access_method_name(&ad.access_method.to_id_string()),
+                        location: general_name_string(&ad.access_location),
+                    })
+                    .collect();
+            }
+            ParsedExtension::CRLDistributionPoints(dps) => {
+                for dp in dps.iter() {
+                    if let Some(DistributionPointName::FullName(names)) = &dp.distribution_point {
+                        out.crl_distribution_points.extend(names.iter().map(general_name_string));
+                    }
+                }
+            }
+            ParsedExtension::CertificatePolicies(policies) => {
+                out.certificate_policies = policies
+                    .iter()
+                    .map(|p| {
+                        let oid = p.policy_id.to_id_string();
+                        match policy_name(&oid) {
+                            Some(name) => format!("{} ({})", oid, name),
+                            None => oid,
+                        }
+                    })
+                    .collect();
+            }
+            _ => out.other.push(OtherExtension {
+                name: oid2sn(&ext.oid, oid_registry())
+                    .map(str::to_string)
+                    .unwrap_or_else(|_| ext.oid.to_id_string()),
+                critical: ext.critical,
+            }),
+        }
+    }
+    out
+}
+
+const ID_AD_OCSP: &str = "1.3.6.1.5.5.7.48.1";
+const ID_AD_CA_ISSUERS: &str = "1.3.6.1.5.5.7.48.2";
+
+/// caIssuers URIs from the Authority Information Access extension.
+pub fn aia_ca_issuers_urls(cert: &X509Ref) -> Vec<String> {
+    aia_urls(cert, ID_AD_CA_ISSUERS)
+}
+
+/// OCSP responder URIs from the Authority Information Access extension.
+pub fn aia_ocsp_urls(cert: &X509Ref) -> Vec<String> {
+    aia_urls(cert, ID_AD_OCSP)
+}
+
+fn aia_urls(cert: &X509Ref, method: &str) -> Vec<String> {
+    // Use x509-parser for robust AIA parsing
+    let der = match cert.to_der() { Ok(d) => d, Err(_) => return vec![] };
+    let mut urls = Vec::new();
+    if let Ok((_, parsed)) = X509Certificate::from_der(&der) {
+        for ext in parsed.extensions() {
+            if let ParsedExtension::AuthorityInfoAccess(aia) = ext.parsed_extension() {
+                for ad in &aia.accessdescs {
+                    if ad.access_method.to_id_string() == method {
+                        if let GeneralName::URI(uri) = &ad.access_location {
+                            urls.push(uri.to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    urls
+}
+
+/// URIs from the CRL distribution points extension.
+pub fn crl_distribution_urls(cert: &X509Ref) -> Vec<String> {
+    let der = match cert.to_der() { Ok(d) => d, Err(_) => return vec![] };
+    let Ok((_, parsed)) = X509Certificate::from_der(&der) else { return vec![] };
+    let mut urls = Vec::new();
+    for ext in parsed.extensions() {
+        if let ParsedExtension::CRLDistributionPoints(dps) = ext.parsed_extension() {
+            for dp in dps.iter() {
+                if let Some(DistributionPointName::FullName(names)) = &dp.distribution_point {
+                    for name in names {
+                        if let GeneralName::URI(uri) = name {
+                            urls.push(uri.to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    urls
+}
+
+/// OID of the embedded SCT list extension (RFC 6962 section 3.3).
+const SCT_LIST_OID: &str = "1.3.6.1.4.1.11129.2.4.2";
+
+/// Raw TLS-encoded SignedCertificateTimestampList embedded in the certificate.
+pub fn embedded_sct_list(cert: &X509Ref) -> Option<Vec<u8>> {
+    let der = cert.to_der().ok()?;
+    let (_, parsed) = X509Certificate::from_der(&der).ok()?;
+    let ext = parsed.extensions().iter().find(|e| e.oid.to_id_string() == SCT_LIST_OID)?;
+    // extnValue wraps the TLS structure in one more OCTET STRING
+    let (_, inner) = parse_der_octetstring(ext.value).ok()?;
+    inner.as_slice().ok().map(<[u8]>::to_vec)
+}
+
+/// Render a GeneralName the way `openssl x509 -text` does (`DNS:`, `IP:`, `URI:`...).
+pub fn general_name_string(gn: &GeneralName<'_>) -> String {
+    match gn {
+        GeneralName::DNSName(d) => format!("DNS:{}", d),
+        GeneralName::IPAddress(b) => match b.len() {
+            4 => format!("IP:{}", IpAddr::from(<[u8; 4]>::try_from(*b).unwrap_or_default())),
+            16 => format!("IP:{}", IpAddr::from(<[u8; 16]>::try_from(*b).unwrap_or_default())),
+            _ => format!("IP:{}", hex_colon(b)),
+        },
+        GeneralName::URI(u) => format!("URI:{}", u),
+        GeneralName::RFC822Name(e) => format!("email:{}", e),
+        GeneralName::DirectoryName(n) => format!("DirName:{}", n),
+        other => other.to_string(),
+    }
+}
+
+fn access_method_name(oid: &str) -> String {
+    match oid {
+        ID_AD_OCSP => "OCSP".to_string(),
+        ID_AD_CA_ISSUERS => "CA Issuers".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// CA/Browser Forum reserved policy identifiers, the ones worth naming.
+fn policy_name(oid: &str) -> Option<&'static str> {
+    match oid {
+        "2.5.29.32.0" => Some("anyPolicy"),
+        "2.23.140.1.1" => Some("Extended Validation"),
+        "2.23.140.1.2.1" => Some("Domain Validated"),
+        "2.23.140.1.2.2" => Some("Organization Validated"),
+        "2.23.140.1.2.3" => Some("Individual Validated"),
+        _ => None,
+    }
+}
@@ -0,0 +1,213 @@
+use foreign_types::ForeignTypeRef;
+use openssl::asn1::Asn1GeneralizedTimeRef;
+use openssl::error::ErrorStack;
+use openssl::nid::Nid;
+use openssl::ocsp::{OcspBasicResponseRef, OcspCertIdRef, OcspCertStatus, OcspRevokedStatus};
+use openssl::ssl::{SslCipherRef, SslRef};
+use openssl::x509::X509NameRef;
+use std::env;
+use std::ffi::{c_char, c_int, c_long, c_uchar, c_void, CStr};
+use std::path::PathBuf;
+use std::ptr::null_mut;
+
+// The libssl/libcrypto calls the openssl crate does not bind, each behind a
+// safe wrapper. Null results become `None` or an empty list, and anything
+// borrowed borrows from the object it was read from.
+
+/// `SCT_get_source` values.
+pub const SCT_SOURCE_TLS_EXTENSION: c_int = 1;
+pub const SCT_SOURCE_OCSP_STAPLED_RESPONSE: c_int = 3;
+
+const SSL_CTRL_GET_PEER_SIGNATURE_NID: c_int = 108;
+const SSL_CT_VALIDATION_PERMISSIVE: c_int = 0;
+
+/// Key type of the server's handshake signature.
+pub fn peer_signature_type(ssl: &SslRef) -> Option<Nid> {
+    let mut nid: c_int = 0;
+    // SAFETY: `ssl` is a live SSL; the call only writes the out-parameter.
+    let found = unsafe { sys::SSL_get_peer_signature_type_nid(ssl.as_ptr(), &mut nid) } == 1;
+    found.then(|| Nid::from_raw(nid))
+}
+
+/// Digest of the server's handshake signature (none for EdDSA).
+pub fn peer_signature_digest(ssl: &SslRef) -> Option<Nid> {
+    let mut nid: c_int = 0;
+    // SAFETY: `ssl` is a live SSL; the control only writes the out-parameter.
+    let found = unsafe {
+        openssl_sys::SSL_ctrl(ssl.as_ptr(), SSL_CTRL_GET_PEER_SIGNATURE_NID, 0, &mut nid as *mut c_int as *mut c_void)
+    } == 1;
+    (found && nid != 0).then(|| Nid::from_raw(nid))
+}
+
+/// CA names of the server's CertificateRequest, in its order.
+pub fn client_ca_names(ssl: &SslRef) -> Vec<&X509NameRef> {
+    // SAFETY: the stack (or null) holds X509_NAMEs owned by `ssl`.
+    unsafe { stack_items(sys::SSL_get_client_CA_list(ssl.as_ptr()), ssl) }
+}
+
+/// Signature schemes of the server's CertificateRequest, as TLS code points.
+pub fn peer_sigalgs(ssl: &SslRef) -> Vec<u16> {
+    // SAFETY: `ssl` is a live SSL; index -1 only returns the count and every
+    // later call only writes its out-parameters.
+    unsafe {
+        let count = sys::SSL_get_sigalgs(ssl.as_ptr(), -1, null_mut(), null_mut(), null_mut(), null_mut(), null_mut());
+        (0..count)
+            .map(|i| {
+                let (mut sig, mut hash): (c_uchar, c_uchar) = (0, 0);
+                sys::SSL_get_sigalgs(ssl.as_ptr(), i, null_mut(), null_mut(), null_mut(), &mut sig, &mut hash);
+                u16::from_be_bytes([hash, sig])
+            })
+            .collect()
+    }
+}
+
+/// Cipher suites `ssl` would offer, in preference order.
+pub fn ciphers(ssl: &SslRef) -> Vec<&SslCipherRef> {
+    // SAFETY: the stack (or null) holds SSL_CIPHERs, static tables in libssl.
+    unsafe { stack_items(sys::SSL_get_ciphers(ssl.as_ptr()), ssl) }
+}
+
+/// Send the signed_certificate_timestamp extension, without letting
+/// OpenSSL abort the handshake over the SCTs it gets back.
+pub fn enable_ct_permissive(ssl: &mut SslRef) -> Result<(), ErrorStack> {
+    // SAFETY: `ssl` is a live SSL, exclusively borrowed.
+    match unsafe { sys::SSL_enable_ct(ssl.as_ptr(), SSL_CT_VALIDATION_PERMISSIVE) } {
+        1 => Ok(()),
+        _ => Err(ErrorStack::get()),
+    }
+}
+
+/// SCTs collected from the handshake: `SCT_get_source` value and the
+/// TLS-encoded SCT.
+pub fn peer_scts(ssl: &SslRef) -> Vec<(c_int, Vec<u8>)> {
+    // SAFETY: the stack (or null) holds SCTs owned by `ssl`, which outlives
+    // this function; i2o_SCT writes exactly the length it reported.
+    unsafe {
+        let stack = sys::SSL_get0_peer_scts(ssl.as_ptr());
+        let mut out = Vec::new();
+        if stack.is_null() {
+            return out;
+        }
+        for i in 0..openssl_sys::OPENSSL_sk_num(stack) {
+            let sct = openssl_sys::OPENSSL_sk_value(stack, i) as *const c_void;
+            let len = sys::i2o_SCT(sct, null_mut());
+            if len <= 0 {
+                continue;
+            }
+            let mut data = vec![0u8; len as usize];
+            let mut p = data.as_mut_ptr();
+            if sys::i2o_SCT(sct, &mut p) == len {
+                out.push((sys::SCT_get_source(sct), data));
+            }
+        }
+        out
+    }
+}
+
+/// OpenSSL's default hashed certificate directories: `SSL_CERT_DIR` when
+/// set, else the directory it was built with.
+pub fn default_cert_dirs() -> Vec<PathBuf> {
+    // SAFETY: both return null or a static NUL-terminated string.
+    let (env_name, dir) = unsafe {
+        let cstr = |p: *const c_char| (!p.is_null()).then(|| CStr::from_ptr(p).to_string_lossy().into_owned());
+        (cstr(openssl_sys::X509_get_default_cert_dir_env()), cstr(openssl_sys::X509_get_default_cert_dir()))
+    };
+    match env_name.and_then(env::var_os) {
+        Some(dirs) => env::split_paths(&dirs).collect(),
+        None => dir.into_iter().map(PathBuf::from).collect(),
+    }
+}
+
+/// One SingleResponse of a basic OCSP response.
+pub struct SingleStatus<'a> {
+    pub status: OcspCertStatus,
+    pub reason: OcspRevokedStatus,
+    pub revocation_time: Option<&'a Asn1GeneralizedTimeRef>,
+    pub this_update: Option<&'a Asn1GeneralizedTimeRef>,
+    pub next_update: Option<&'a Asn1GeneralizedTimeRef>,
+}
+
+impl SingleStatus<'_> {
+    /// Whether now is within thisUpdate/nextUpdate, give or take `slack_secs`.
+    pub fn is_current(&self, slack_secs: i64) -> bool {
+        let (Some(this_update), next_update) = (self.this_update, self.next_update) else {
+            return false;
+        };
+        let next = next_update.map_or(null_mut(), |t| t.as_ptr());
+        // SAFETY: both times are borrowed from the live response.
+        unsafe { openssl_sys::OCSP_check_validity(this_update.as_ptr(), next, slack_secs as c_long, -1) == 1 }
+    }
+}
+
+/// Status of `id` in `basic`. Unlike `OcspBasicResponseRef::find_status`,
+/// the revocation reason is the reason and nextUpdate may be absent.
+pub fn find_status<'a>(basic: &'a OcspBasicResponseRef, id: &OcspCertIdRef) -> Option<SingleStatus<'a>> {
+    let mut status = openssl_sys::V_OCSP_CERTSTATUS_UNKNOWN;
+    let mut reason = openssl_sys::OCSP_REVOKED_STATUS_NOSTATUS;
+    let mut revocation_time = null_mut();
+    let mut this_update = null_mut();
+    let mut next_update = null_mut();
+    // SAFETY: pointers come from live wrappers; the out-params point into
+    // `basic`, whose lifetime the returned times carry.
+    unsafe {
+        let found = openssl_sys::OCSP_resp_find_status(
+            basic.as_ptr(),
+            id.as_ptr(),
+            &mut status,
+            &mut reason,
+            &mut revocation_time,
+            &mut this_update,
+            &mut next_update,
+        );
+        if found != 1 {
+            return None;
+        }
+        let time = |p: *mut openssl_sys::ASN1_GENERALIZEDTIME| (!p.is_null()).then(|| Asn1GeneralizedTimeRef::from_ptr(p));
+        Some(SingleStatus {
+            status: OcspCertStatus::from_raw(status),
+            reason: OcspRevokedStatus::from_raw(reason),
+            revocation_time: time(revocation_time),
+            this_update: time(this_update),
+            next_update: time(next_update),
+        })
+    }
+}
+
+// Items of an OpenSSL stack, borrowed for as long as `owner`.
+//
+// SAFETY: `stack` must be null or a live stack of `T` that `owner` keeps
+// alive and unchanged.
+unsafe fn stack_items<'a, T: ForeignTypeRef + 'a, O: ?Sized>(
+    stack: *const openssl_sys::OPENSSL_STACK,
+    _owner: &'a O,
+) -> Vec<&'a T> {
+    if stack.is_null() {
+        return Vec::new();
+    }
+    (0..openssl_sys::OPENSSL_sk_num(stack))
+        .map(|i| T::from_ptr(openssl_sys::OPENSSL_sk_value(stack, i) as *mut T::CType))
+        .collect()
+}
+
+mod sys {
+    use std::ffi::{c_int, c_uchar, c_void};
+
+    extern "C" {
+        pub fn SSL_get_peer_signature_type_nid(s: *const openssl_sys::SSL, pnid: *mut c_int) -> c_int;
+        pub fn SSL_get_client_CA_list(s: *const openssl_sys::SSL) -> *const openssl_sys::OPENSSL_STACK;
+        pub fn SSL_get_sigalgs(
+            s: *mut openssl_sys::SSL,
+            idx: c_int,
+            psign: *mut c_int,
+            phash: *mut c_int,
+            psignhash: *mut c_int,
+            rsig: *mut c_uchar,
+            rhash: *mut c_uchar,
+        ) -> c_int;
+        pub fn SSL_get_ciphers(s: *const openssl_sys::SSL) -> *const openssl_sys::OPENSSL_STACK;
+        pub fn SSL_enable_ct(s: *mut openssl_sys::SSL, validation_mode: c_int) -> c_int;
+        pub fn SSL_get0_peer_scts(s: *mut openssl_sys::SSL) -> *const openssl_sys::OPENSSL_STACK;
+        pub fn SCT_get_source(sct: *const c_void) -> c_int;
+        pub fn i2o_SCT(sct: *const c_void, out: *mut *mut u8) -> c_int;
+    }
+}
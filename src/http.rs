@@ -0,0 +1,16 @@
+use anyhow::Result;
+use crate::proxy::Proxy;
+use reqwest::blocking::Client;
+
+/// Blocking HTTP client shared by AIA, OCSP and CRL fetches, going through
+/// `proxy` when one is configured.
+pub fn client(proxy: Option<&Proxy>) -> Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent("tls-doctor/1.0")
+        .redirect(reqwest::redirect::Policy::limited(5))
+        .timeout(std::time::Duration::from_secs(15));
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy.for_http()?);
+    }
+    Ok(builder.build()?)
+}
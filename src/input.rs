@@ -0,0 +1,71 @@
+use anyhow::{bail, Context, Result};
+use crate::exit::Failure;
+use crate::report::InputFormat;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkcs7::Pkcs7;
+use openssl::x509::X509;
+use std::fs;
+use std::path::Path;
+
+// Certificate input shared by `diag --file` and `scaffold`: the format is
+// detected from the content, not the file extension.
+
+/// Read every certificate of `path`, detecting the format. `password` opens
+/// PKCS#12 files.
+pub fn load_certificates(path: &Path, password: Option<&str>) -> Result<(InputFormat, Vec<X509>)> {
+    let data = fs::read(path).with_context(|| Failure::Input(format!("failed to read {}", path.display())))?;
+    let (format, certs) = parse_certificates(&data, password)
+        .with_context(|| Failure::Input(format!("failed to parse certificates from {}", path.display())))?;
+    if certs.is_empty() {
+        return Err(Failure::Input(format!("no certificates found in {} ({})", path.display(), format_label(format))).into());
+    }
+    Ok((format, certs))
+}
+
+/// Certificates of a PEM bundle, a DER certificate, a PKCS#7 bundle (PEM or
+/// DER) or a PKCS#12 file, in file order.
+pub fn parse_certificates(data: &[u8], password: Option<&str>) -> Result<(InputFormat, Vec<X509>)> {
+    if contains(data, b"-----BEGIN PKCS7-----") {
+        let p7 = Pkcs7::from_pem(data).context("malformed PEM PKCS#7 bundle")?;
+        return Ok((InputFormat::Pkcs7Pem, pkcs7_certificates(&p7)));
+    }
+    if contains(data, b"-----BEGIN ") {
+        return Ok((InputFormat::Pem, X509::stack_from_pem(data).context("malformed PEM certificate")?));
+    }
+    if let Ok(cert) = X509::from_der(data) {
+        return Ok((InputFormat::Der, vec![cert]));
+    }
+    if let Ok(p7) = Pkcs7::from_der(data) {
+        return Ok((InputFormat::Pkcs7Der, pkcs7_certificates(&p7)));
+    }
+    if let Ok(p12) = Pkcs12::from_der(data) {
+        let Ok(parsed) = p12.parse2(password.unwrap_or_default()) else {
+            bail!("cannot open PKCS#12 file: wrong or missing --password");
+        };
+        let mut certs: Vec<X509> = parsed.cert.into_iter().collect();
+        certs.extend(parsed.ca.into_iter().flatten());
+        return Ok((InputFormat::Pkcs12, certs));
+    }
+    bail!("not a PEM, DER, PKCS#7 or PKCS#12 certificate file")
+}
+
+pub fn format_label(format: InputFormat) -> &'static str {
+    match format {
+        InputFormat::Pem => "PEM",
+        InputFormat::Der => "DER",
+        InputFormat::Pkcs7Pem => "PKCS#7 (PEM)",
+        InputFormat::Pkcs7Der => "PKCS#7 (DER)",
+        InputFormat::Pkcs12 => "PKCS#12",
+    }
+}
+
+fn pkcs7_certificates(p7: &Pkcs7) -> Vec<X509> {
+    p7.signed()
+        .and_then(|signed| signed.certificates())
+        .map(|stack| stack.iter().map(|c| c.to_owned()).collect())
+        .unwrap_or_default()
+}
+
+fn contains(data: &[u8], needle: &[u8]) -> bool {
+    data.windows(needle.len()).any(|w| w == needle)
+}
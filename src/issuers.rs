@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use crate::exit::Failure;
+use crate::input::parse_certificates;
+use openssl::sha::sha256;
+use openssl::x509::{X509NameRef, X509};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Issuer certificates available without the network: the on-disk cache of
+// earlier AIA fetches and a user-supplied directory of intermediates.
+
+/// Persistent cache of fetched issuers. Each AIA response is stored under
+/// its URL, and each certificate under its subject, key identifier and
+/// digest so it can be found again without knowing the URL.
+#[derive(Debug, Clone)]
+pub struct IssuerCache {
+    dir: PathBuf,
+}
+
+impl IssuerCache {
+    pub fn new(dir: PathBuf) -> IssuerCache {
+        IssuerCache { dir }
+    }
+
+    /// `$XDG_CACHE_HOME/tls-doctor/issuers`, else `~/.cache/tls-doctor/issuers`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CACHE_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(base.join("tls-doctor").join("issuers"))
+    }
+
+    /// Certificates last fetched from `url`.
+    pub fn by_url(&self, url: &str) -> Option<Vec<X509>> {
+        let data = fs::read(self.dir.join(format!("url-{}.pem", hex(&sha256(url.as_bytes()))))).ok()?;
+        X509::stack_from_pem(&data).ok().filter(|certs| !certs.is_empty())
+    }
+
+    /// Every cached certificate named `subject`.
+    pub fn by_subject(&self, subject: &X509NameRef) -> Vec<X509> {
+        let prefix = format!("subject-{}-", subject_key(subject));
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+            .filter_map(|e| fs::read(e.path()).ok())
+            .filter_map(|data| X509::from_pem(&data).ok())
+            .collect()
+    }
+
+    /// Remember what `url` served. Best effort: a cache that cannot be
+    /// written only costs a fetch next time.
+    pub fn store(&self, url: &str, certs: &[X509]) {
+        if fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+        let mut pem = Vec::new();
+        for cert in certs {
+            let Ok(data) = cert.to_pem() else { continue };
+            let name = format!("subject-{}-{}.pem", subject_key(cert.subject_name()), cert_key(cert));
+            write_atomic(&self.dir.join(name), &data);
+            pem.extend_from_slice(&data);
+        }
+        write_atomic(&self.dir.join(format!("url-{}.pem", hex(&sha256(url.as_bytes())))), &pem);
+    }
+}
+
+/// Every certificate in the PEM, DER and PKCS#7 files of `dir` (not recursive).
+pub fn load_issuer_dir(dir: &Path) -> Result<Vec<X509>> {
+    let entries = fs::read_dir(dir)
+        .with_context(|| Failure::Input(format!("failed to read intermediates directory {}", dir.display())))?;
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.is_file()).collect();
+    paths.sort();
+    let mut certs = Vec::new();
+    for path in paths {
+        // Other files (keys, READMEs) are skipped
+        if let Ok((_, found)) = fs::read(&path).map_err(anyhow::Error::from).and_then(|d| parse_certificates(&d, None)) {
+            certs.extend(found);
+        }
+    }
+    Ok(certs)
+}
+
+fn subject_key(subject: &X509NameRef) -> String {
+    hex(&sha256(&subject.to_der().unwrap_or_default())[..16])
+}
+
+// Subject Key Identifier, when present, and a digest of the certificate:
+// cross-signed variants of a CA share the first but not the second.
+fn cert_key(cert: &X509) -> String {
+    let digest = hex(&sha256(&cert.to_der().unwrap_or_default())[..16]);
+    match cert.subject_key_id() {
+        Some(ski) => format!("{}-{}", hex(ski.as_slice()), digest),
+        None => digest,
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Readers never see a half-written file.
+fn write_atomic(path: &Path, data: &[u8]) {
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    if fs::write(&tmp, data).is_ok() && fs::rename(&tmp, path).is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+}
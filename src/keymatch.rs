@@ -0,0 +1,65 @@
+use anyhow::{Context, Result};
+use crate::exit::Failure;
+use crate::report::{Issue, KeyCheck};
+use crate::util::{fingerprint_sha256, format_name_human};
+use openssl::pkey::{Id as KeyId, PKey, PKeyRef, Private};
+use openssl::x509::X509Ref;
+use std::fs;
+use std::path::Path;
+
+// Private key / leaf certificate pairing, for `diag --file --key` and
+// `keymatch`.
+
+/// Read a private key file; see `parse_private_key`.
+pub fn load_private_key(path: &Path, passphrase: Option<&str>) -> Result<PKey<Private>> {
+    let data = fs::read(path).with_context(|| Failure::Input(format!("failed to read key {}", path.display())))?;
+    parse_private_key(&data, passphrase)
+        .with_context(|| Failure::Input(format!("failed to load a private key from {}", path.display())))
+}
+
+/// RSA, EC, Ed25519 or Ed448 key in PEM (PKCS#8 or traditional, possibly
+/// encrypted with `passphrase`) or DER PKCS#8.
+pub fn parse_private_key(data: &[u8], passphrase: Option<&str>) -> Result<PKey<Private>> {
+    if !data.windows(11).any(|w| w == b"-----BEGIN ") {
+        return PKey::private_key_from_der(data).context("not a PEM key nor a DER PKCS#8 key");
+    }
+    let encrypted = data.windows(9).any(|w| w == b"ENCRYPTED");
+    match (encrypted, passphrase) {
+        (true, None) => anyhow::bail!("key is encrypted; a passphrase is required"),
+        (true, Some(pass)) => PKey::private_key_from_pem_passphrase(data, pass.as_bytes()).context("wrong passphrase"),
+        (false, _) => PKey::private_key_from_pem(data).context("malformed PEM key"),
+    }
+}
+
+/// Compare `key` with the public key of `leaf`.
+pub fn check_key(leaf: &X509Ref, key: &PKeyRef<Private>) -> KeyCheck {
+    KeyCheck {
+        key: describe_key(key),
+        leaf: format_name_human(leaf.subject_name().entries()),
+        leaf_fingerprint: fingerprint_sha256(leaf).unwrap_or_default(),
+        matches: leaf.public_key().is_ok_and(|public| public.public_eq(key)),
+    }
+}
+
+/// Error when the key belongs to another certificate.
+pub fn key_issues(check: &KeyCheck) -> Vec<Issue> {
+    if check.matches {
+        return Vec::new();
+    }
+    vec![Issue::error(format!(
+        "private key ({}) does not match the leaf certificate {}",
+        check.key, check.leaf
+    ))]
+}
+
+// e.g. "RSA 2048 bits", "EC 256 bits", "Ed25519".
+fn describe_key(key: &PKeyRef<Private>) -> String {
+    match key.id() {
+        KeyId::RSA => format!("RSA {} bits", key.bits()),
+        KeyId::EC => format!("EC {} bits", key.bits()),
+        KeyId::DSA => format!("DSA {} bits", key.bits()),
+        KeyId::ED25519 => "Ed25519".to_string(),
+        KeyId::ED448 => "Ed448".to_string(),
+        other => format!("{:?}", other),
+    }
+}
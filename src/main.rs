@@ -1,198 +1,593 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use openssl::hash::MessageDigest;
-use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use openssl::ssl::{HandshakeError, SslConnector, SslRef, SslStream, SslVerifyMode, SslVersion, StatusType};
 use openssl::x509::{X509Ref, X509};
-use std::net::TcpStream;
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::fs;
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use clap::Parser;
-use std::io::Write;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
+mod addresses;
 mod cli;
 mod chain;
+mod client_auth;
+mod crl;
+mod exit;
+mod export;
+mod extensions;
+mod ffi;
+mod http;
+mod keymatch;
+mod input;
+mod issuers;
 mod validate;
+mod negotiation;
+mod net;
+mod ocsp;
 mod print;
+mod proxy;
+mod report;
 mod util;
 mod scaffold;
+mod scan;
+mod sct;
+mod starttls;
 
+use crate::addresses::{address_issues, check_addresses, resolve_all};
 use crate::chain::order_chain_leaf_to_root;
-use crate::cli::{Cli, Command};
-use crate::print::{print_cert_info, print_chain_with_separator, print_bold};
-use crate::validate::{validate_and_report, validate_chain};
-use crate::util::{issuer_cn, subject_cn};
-use crate::scaffold::{build_bundle_from_leaf_file, write_pem_bundle};
+use crate::client_auth::{await_verdict, certificate_request, client_auth_issues, describe_request, ClientIdentity};
+use crate::cli::{BundleFormat, Cli, Command, OutputFormat, ScaffoldArgs, StartTls};
+use crate::crl::{check_crls, crl_issues, load_crl_files};
+use crate::exit::Failure;
+use crate::export::{write_bundle, ExportOptions};
+use crate::input::{format_label, load_certificates};
+use crate::issuers::{load_issuer_dir, IssuerCache};
+use crate::keymatch::{check_key, key_issues, load_private_key};
+use crate::negotiation::{
+    connector_builder, is_legacy_protocol, legacy_connector_builder, negotiation, negotiation_issues, ALPN_PROTOCOLS,
+};
+use crate::net::{handshake_failure, is_legacy_refusal, ssl_failure, with_retries, Family, NetOptions};
+use crate::ocsp::{check_ocsp, check_staple, ocsp_issues, staple_issues};
+use crate::print::{cert_info, print_json, print_key_check, print_report, print_scan};
+use crate::proxy::{split_addr, LazyProxy};
+use crate::report::{AddressCheck, DiagReport, InputFormat, Issue, KeyCheck};
+use crate::validate::{
+    bundle_issues, chain_verdict, check_hostname, expiry_issues, hostname_issue, ExpiryThresholds, LazyTrustStore,
+    TrustOptions, TrustStore,
+};
+use crate::scaffold::{
+    add_trust_anchor, build_bundle_from_leaf_file, diff_bundles, exclude_root, repair_bundle, ScaffoldOptions,
+};
+use crate::scan::scan_server;
+use crate::starttls::upgrade;
+use crate::util::cn_label;
+use crate::sct::{check_scts, load_log_list, peer_scts, request_scts, sct_issues, RawSct};
 
 /// Entry point wiring CLI, network handshake, printing, and validation.
-
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    match &cli.command {
-    Command::Diag(args) => run_diag(args)?,
-    Command::Scaffold(args) => run_scaffold(args)?,
+    let result = match &cli.command {
+    Command::Diag(args) => run_diag(args),
+    Command::Scan(args) => run_scan(args),
+    Command::Keymatch(args) => run_keymatch(args),
+    Command::Scaffold(args) => run_scaffold(args).map(|()| ExitCode::SUCCESS),
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            exit::for_error(&e)
+        }
     }
+}
 
-    Ok(())
+/// Checks shared by live and file diagnosis, resolved once from the CLI.
+#[derive(Debug, Default)]
+struct DiagOptions {
+    trust: TrustOptions,
+    /// Name the leaf must cover (`--hostname`).
+    hostname: Option<String>,
+    expiry: ExpiryThresholds,
+    /// Query the leaf's OCSP responder.
+    ocsp: bool,
+    /// Check CRLs, from `crl_files` only when given, else the distribution points.
+    crl: bool,
+    crl_files: Vec<PathBuf>,
+    /// Inspect the leaf's SCTs, verifying them against `ct_logs` when given.
+    sct: bool,
+    ct_logs: Option<PathBuf>,
+    net: NetOptions,
+    /// Tunnel the connection and HTTP fetches through this proxy.
+    proxy: LazyProxy,
+    /// Certificate presented when the server asks for one (`--client-cert`).
+    client: Option<ClientIdentity>,
+    /// Opens a PKCS#12 `--file`.
+    password: Option<String>,
+    /// Private key to compare with the leaf of `--file`, and its passphrase.
+    key: Option<PathBuf>,
+    key_password: Option<String>,
 }
 
-fn run_diag(args: &crate::cli::DiagArgs) -> Result<()> {
-    if let Some(file) = &args.file {
-        return run_with_file(file);
+fn run_diag(args: &crate::cli::DiagArgs) -> Result<ExitCode> {
+    let opts = DiagOptions {
+        trust: TrustOptions {
+            ca_file: args.ca_file.clone(),
+            ca_dir: args.ca_dir.clone(),
+            system: !args.no_system_trust,
+        },
+        hostname: args.hostname.clone(),
+        expiry: ExpiryThresholds { warn_days: args.warn_days, crit_days: args.crit_days },
+        ocsp: args.ocsp,
+        crl: args.crl_check || !args.crl.is_empty(),
+        crl_files: args.crl.clone(),
+        sct: args.sct || args.ct_logs.is_some(),
+        ct_logs: args.ct_logs.clone(),
+        net: NetOptions {
+            connect_timeout: args.connect_timeout,
+            handshake_timeout: args.handshake_timeout,
+            retries: args.retries,
+            family: match (args.ipv4, args.ipv6) {
+                (true, _) => Some(Family::V4),
+                (_, true) => Some(Family::V6),
+                _ => None,
+            },
+        },
+        proxy: LazyProxy::new(args.proxy.as_deref()),
+        client: match &args.client_cert {
+            Some(cert) => Some(ClientIdentity::load(cert, args.client_key.as_deref(), args.client_password.as_deref())?),
+            None => None,
+        },
+        password: args.password.clone(),
+        key: args.key.clone(),
+        key_password: args.key_password.clone(),
+    };
+    if opts.expiry.crit_days > opts.expiry.warn_days {
+        anyhow::bail!("--crit-days ({}) must not exceed --warn-days ({})", args.crit_days, args.warn_days);
     }
+    let report = match &args.file {
+        Some(file) => diagnose_file(file, &opts)?,
+        None => diagnose_server(args, &opts)?,
+    };
+    match args.output {
+        OutputFormat::Text => print_report(&report, args.verbose)?,
+        OutputFormat::Json => print_json(&report)?,
+    }
+    Ok(exit::for_status(report.verdict.status))
+}
 
+fn diagnose_server(args: &crate::cli::DiagArgs, opts: &DiagOptions) -> Result<DiagReport> {
     let server = args.server.as_ref().expect("clap enforces one of --server/--file");
-    let addr = format!("{}:{}", server, args.port);
-    let tcp = TcpStream::connect(&addr)
-        .with_context(|| format!("failed to connect to {}", addr))?;
+    let default_port = args.port.unwrap_or_else(|| args.starttls.map_or(443, StartTls::default_port));
+    // One load of the anchors for every handshake and the report
+    let (connector, trust) = diag_connector(args, opts, TrustStore::load(&opts.trust)?)?;
+    let connect = |addr: &str| connect_tls(args, opts, &connector, addr).map_err(|e| explain_legacy_server(args, opts, addr, e));
+
+    let mut addresses = None;
+    let (addr, ssl_stream) = if args.all_addresses {
+        let resolved = resolve_all(server, default_port, opts.net.family)?;
+        let (checks, addr, stream) = sweep_addresses(&resolved, &trust, connect)?;
+        addresses = Some(checks);
+        (addr, stream)
+    } else {
+        let addr = match args.connect {
+            Some(target) => SocketAddr::new(target.ip, target.port.unwrap_or(default_port)).to_string(),
+            None => format!("{}:{}", server, default_port),
+        };
+        let stream = connect(&addr)?;
+        (addr, stream)
+    };
+
+    let certs = served_chain(ssl_stream.ssl());
+    let seq: Vec<&X509Ref> = certs.iter().map(|c| c.as_ref()).collect();
+
+    let staple = check_staple(ssl_stream.ssl().ocsp_status(), &seq, &trust);
+
+    let delivered = if opts.sct { peer_scts(ssl_stream.ssl()) } else { Vec::new() };
+    let negotiated = negotiation(ssl_stream.ssl());
+    let client_auth = certificate_request(ssl_stream.ssl(), opts.client.as_ref());
+    let mut issues = negotiation_issues(&negotiated);
+    issues.extend(client_auth_issues(&client_auth));
+    issues.extend(staple_issues(&staple));
+    // Drop connection immediately after collecting the chain.
+    drop(ssl_stream);
+    if let Some(checks) = &addresses {
+        issues.extend(address_issues(checks));
+    }
+
+    let mut report = build_report(addr, &seq, &[], &delivered, issues, opts, &trust)?;
+    report.staple = Some(staple);
+    report.negotiation = Some(negotiated);
+    report.addresses = addresses;
+    report.client_auth = Some(client_auth);
+    Ok(report)
+}
+
+// `--all-addresses`: handshake with every address first, keeping the session
+// of the first one that succeeds for the full report. The others failing are
+// address issues; only when none succeeds is the first failure returned.
+fn sweep_addresses(
+    addrs: &[SocketAddr],
+    trust: &TrustStore,
+    connect: impl Fn(&str) -> Result<SslStream<TcpStream>>,
+) -> Result<(Vec<AddressCheck>, String, SslStream<TcpStream>)> {
+    let first = RefCell::new(None);
+    let first_err = RefCell::new(None);
+    let checks = check_addresses(addrs, trust, |a| match connect(a) {
+        Ok(stream) => {
+            let chain = served_chain(stream.ssl());
+            first.borrow_mut().get_or_insert_with(|| (a.to_string(), stream));
+            Ok(chain)
+        }
+        Err(e) => {
+            let shown = anyhow!("{:#}", e);
+            first_err.borrow_mut().get_or_insert(e);
+            Err(shown)
+        }
+    });
+    match (first.into_inner(), first_err.into_inner()) {
+        (Some((addr, stream)), _) => Ok((checks, addr, stream)),
+        (None, Some(err)) => Err(err),
+        (None, None) => unreachable!("resolve_all returns at least one address"),
+    }
+}
 
-    let mut builder = SslConnector::builder(SslMethod::tls())?;
+// Connector for every handshake of a diagnosis, verifying against `trust`
+// (the same anchors as the report, returned for it).
+fn diag_connector(args: &crate::cli::DiagArgs, opts: &DiagOptions, trust: TrustStore) -> Result<(SslConnector, TrustStore)> {
+    let mut builder = connector_builder()?;
     if args.insecure {
         builder.set_verify(SslVerifyMode::NONE);
     }
-    let connector = builder.build();
+    if let Some(identity) = &opts.client {
+        identity.configure(&mut builder)?;
+    }
+    trust.build_connector(builder)
+}
+
+// A server that only speaks TLS 1.0/1.1 fails the default-level handshake
+// with a bare protocol error. Find out with an unverified legacy handshake,
+// used for nothing else, and say so in the error.
+fn explain_legacy_server(args: &crate::cli::DiagArgs, opts: &DiagOptions, addr: &str, err: anyhow::Error) -> anyhow::Error {
+    if !is_legacy_refusal(&err) {
+        return err;
+    }
+    let Ok(mut builder) = legacy_connector_builder() else { return err };
+    builder.set_verify(SslVerifyMode::NONE);
+    let Ok(stream) = connect_tls(args, opts, &builder.build(), addr) else { return err };
+    let protocol = stream.ssl().version_str();
+    if !is_legacy_protocol(protocol) {
+        return err;
+    }
+    err.context(Failure::Connection(format!(
+        "{} only negotiates deprecated protocol {}, below OpenSSL's default security level; `tls-doctor scan` lists what it accepts",
+        addr, protocol
+    )))
+}
 
-    // For SNI and hostname verification, pass the hostname (not host:port)
-    let hostname = server.as_str();
-    let ssl_stream = connector
-        .connect(hostname, tcp)
-        .with_context(|| format!("TLS handshake with {} failed", addr))?;
+// TCP connect to `addr`, STARTTLS upgrade if asked, then the handshake for
+// the SNI name.
+fn connect_tls(
+    args: &crate::cli::DiagArgs,
+    opts: &DiagOptions,
+    connector: &SslConnector,
+    addr: &str,
+) -> Result<SslStream<TcpStream>> {
+    let server = args.server.as_ref().expect("clap enforces one of --server/--file");
+    // Name the certificate is requested for and verified against
+    let hostname = args.sni.as_deref().unwrap_or(server);
 
-    // Grab the peer certificate chain (includes leaf). Some servers may not send intermediates.
-    let chain = ssl_stream.ssl().peer_cert_chain();
+    with_retries(opts.net.retries, || {
+        let mut tcp = match (opts.proxy.get()?, split_addr(addr)) {
+            (Some(proxy), Some((host, port))) if !proxy.bypasses(host) => proxy.tunnel(host, port, &opts.net)?,
+            _ => net::connect(addr, &opts.net)?,
+        };
+        if let Some(proto) = args.starttls {
+            upgrade(&mut tcp, proto, hostname)?;
+        }
 
-    let mut stdout = StandardStream::stdout(ColorChoice::Auto);
-    writeln!(&mut stdout, "--- Certificate chain (leaf -> root) ---")?;
+        // For SNI and hostname verification, pass the hostname (not host:port).
+        // An explicit --hostname is checked by us and reported as an issue instead.
+        let mut config = connector
+            .configure()?
+            .verify_hostname(opts.hostname.is_none())
+            .use_server_name_indication(!args.no_sni);
+        // Ask for a stapled OCSP response (status_request)
+        config.set_status_type(StatusType::OCSP)?;
+        config.set_alpn_protos(ALPN_PROTOCOLS)?;
+        if opts.sct {
+            request_scts(&mut config)?;
+        }
+        let mut stream = match config.connect(hostname, tcp) {
+            Ok(stream) => stream,
+            Err(e) => {
+                // Say what the server asked for when it rejected our answer
+                let request = match &e {
+                    HandshakeError::Failure(mid) => Some(certificate_request(mid.ssl(), opts.client.as_ref())),
+                    _ => None,
+                };
+                let err = handshake_failure(addr, &opts.net, e);
+                return Err(match request.filter(|r| r.requested) {
+                    Some(r) => err.context(describe_request(&r)),
+                    None => err,
+                });
+            }
+        };
+        let request = certificate_request(stream.ssl(), opts.client.as_ref());
+        if request.requested && stream.ssl().version2() == Some(SslVersion::TLS1_3) {
+            if let Err(e) = await_verdict(&mut stream) {
+                return Err(ssl_failure(addr, &opts.net, e).context(describe_request(&request)));
+            }
+        }
+        Ok(stream)
+    })
+}
 
-    // Build the sequence leaf -> chain (skipping duplicate leaf if present)
-    let leaf_opt = ssl_stream.ssl().peer_certificate();
-    let leaf_fp = leaf_opt
+// Peer certificate chain, leaf first. Some servers may not send intermediates,
+// and the leaf is skipped if the server repeats it in the chain.
+fn served_chain(ssl: &SslRef) -> Vec<X509> {
+    let mut certs: Vec<X509> = Vec::new();
+    let leaf = ssl.peer_certificate();
+    let leaf_fp = leaf
         .as_ref()
         .and_then(|c| c.digest(MessageDigest::sha256()).ok())
         .map(|d| d.to_vec());
-
-    let mut seq: Vec<&X509Ref> = Vec::new();
-    if let Some(leaf) = leaf_opt.as_ref() {
-        seq.push(leaf);
-    }
-    if let Some(stack) = chain {
+    certs.extend(leaf);
+    if let Some(stack) = ssl.peer_cert_chain() {
         for cert in stack {
             if let Some(ref lf) = leaf_fp {
                 if let Ok(d) = cert.digest(MessageDigest::sha256()) {
-                    if &d[..] == &lf[..] {
+                    if d[..] == lf[..] {
                         continue;
                     }
                 }
             }
-            seq.push(cert);
+            certs.push(cert.to_owned());
         }
     }
+    certs
+}
+
+// Offline mode: read a PEM bundle, build a best-effort chain and report issues.
+fn diagnose_file(path: &Path, opts: &DiagOptions) -> Result<DiagReport> {
+    let (format, certs) = load_certificates(path, opts.password.as_deref())?;
+    let key = match &opts.key {
+        Some(key_path) => Some(load_private_key(key_path, opts.key_password.as_deref())?),
+        None => None,
+    };
+
+    // Order certificates: attempt to assemble a leaf->root chain from the set
+    let (seq, unused) = order_chain_leaf_to_root(&certs);
+
+    // Evaluate bundle consistency (unrelated, incomplete, self-verify) before trust
+    let mut issues = bundle_issues(&seq, &unused);
+    let key_check = key.as_ref().zip(seq.first()).map(|(key, leaf)| check_key(leaf, key));
+    issues.extend(key_check.iter().flat_map(key_issues));
+    let trust = TrustStore::load(&opts.trust)?;
+    let mut report = build_report(path.display().to_string(), &seq, &unused, &[], issues, opts, &trust)?;
+    report.input_format = Some(format);
+    report.key = key_check;
+    Ok(report)
+}
+
+// Shared tail of both diagnosis modes: collect certificate fields, run the
+// optional checks and fold everything into the verdict.
+fn build_report(
+    source: String,
+    seq: &[&X509Ref],
+    unused: &[&X509Ref],
+    delivered_scts: &[RawSct],
+    mut issues: Vec<Issue>,
+    opts: &DiagOptions,
+    trust: &TrustStore,
+) -> Result<DiagReport> {
+    let chain = seq
+        .iter()
+        .enumerate()
+        .map(|(i, c)| cert_info(i + 1, c, &opts.expiry))
+        .collect::<Result<Vec<_>>>()?;
+    // Unrelated certificates keep numbering after the chain
+    let unused_info = unused
+        .iter()
+        .enumerate()
+        .map(|(i, c)| cert_info(seq.len() + i + 1, c, &opts.expiry))
+        .collect::<Result<Vec<_>>>()?;
+
+    issues.extend(expiry_issues(seq, &opts.expiry));
+
+    let mut hostname = None;
+    if let (Some(name), Some(leaf)) = (&opts.hostname, seq.first()) {
+        let check = check_hostname(leaf, name);
+        issues.extend(hostname_issue(leaf, &check));
+        hostname = Some(check);
+    }
+
+    let mut ocsp = None;
+    if opts.ocsp {
+        let check = check_ocsp(&http::client(opts.proxy.get()?)?, seq, trust);
+        issues.extend(ocsp_issues(&check));
+        ocsp = Some(check);
+    }
 
-    print_chain_with_separator(&seq)?;
-    validate_and_report(&seq, &[])?;
+    let mut crl = None;
+    if opts.crl {
+        let local = load_crl_files(&opts.crl_files)?;
+        // Local CRLs mean an offline check: no distribution point is fetched
+        let client = if local.is_empty() { Some(http::client(opts.proxy.get()?)?) } else { None };
+        let checks = check_crls(client.as_ref(), seq, &local);
+        issues.extend(crl_issues(&checks));
+        crl = Some(checks);
+    }
 
-    // Drop connection immediately after printing the chain.
+    let mut sct = None;
+    if opts.sct {
+        let logs = match &opts.ct_logs {
+            Some(path) => load_log_list(path)?,
+            None => Vec::new(),
+        };
+        let check = check_scts(seq, delivered_scts, &logs);
+        issues.extend(sct_issues(&check));
+        sct = Some(check);
+    }
 
-    Ok(())
+    let verdict = chain_verdict(seq, issues, trust);
+
+    Ok(DiagReport { source, input_format: None, chain, unused: unused_info, hostname, ocsp, staple: None, crl, sct, negotiation: None, addresses: None, client_auth: None, key: None, verdict })
 }
 
-// Offline mode: read a PEM bundle, build a best-effort chain and report issues.
-fn run_with_file(path: &PathBuf) -> Result<()> {
-    let data = std::fs::read(path)
-        .with_context(|| format!("failed to read PEM bundle from {}", path.display()))?;
-    let certs = X509::stack_from_pem(&data)
-        .with_context(|| format!("failed to parse PEM certificates from {}", path.display()))?;
-    if certs.is_empty() {
-        anyhow::bail!("no certificates found in {}", path.display());
+fn run_keymatch(args: &crate::cli::KeymatchArgs) -> Result<ExitCode> {
+    let check = keymatch(args)?;
+    match args.output {
+        OutputFormat::Text => print_key_check(&check)?,
+        OutputFormat::Json => print_json(&check)?,
     }
+    Ok(ExitCode::from(if check.matches { exit::VALID } else { exit::INVALID }))
+}
 
-    // Order certificates: attempt to assemble a leaf->root chain from the set
-    let (seq, unused) = order_chain_leaf_to_root(&certs);
+// Compare the key with the leaf of the certificate file, wherever the leaf
+// sits in it.
+fn keymatch(args: &crate::cli::KeymatchArgs) -> Result<KeyCheck> {
+    let (_, certs) = load_certificates(&args.cert, args.password.as_deref())?;
+    let key = load_private_key(&args.key, args.key_password.as_deref())?;
+    let (seq, _) = order_chain_leaf_to_root(&certs);
+    let leaf = seq.first().copied().unwrap_or(&certs[0]);
+    Ok(check_key(leaf, &key))
+}
 
-    let mut stdout = StandardStream::stdout(ColorChoice::Auto);
-    writeln!(&mut stdout, "--- Certificate chain (leaf -> root) ---")?;
-    print_chain_with_separator(&seq)?;
-    // Also display unrelated certificates, if any, without "issued by"
-    let mut issues: Vec<String> = Vec::new();
-    let mut next_index = seq.len();
-    if !unused.is_empty() {
-        for cert in &unused {
-            next_index += 1;
-            print_cert_info(next_index, cert)?;
-        }
+fn run_scan(args: &crate::cli::ScanArgs) -> Result<ExitCode> {
+    let net = NetOptions {
+        connect_timeout: args.connect_timeout,
+        handshake_timeout: args.handshake_timeout,
+        ..Default::default()
+    };
+    let report = scan_server(&args.server, args.port, &net)?;
+    match args.output {
+        OutputFormat::Text => print_scan(&report)?,
+        OutputFormat::Json => print_json(&report)?,
     }
+    Ok(exit::for_status(report.status))
+}
+
+// Scaffold subcommand: acknowledge input and output; implementation to follow.
+fn run_scaffold(args: &ScaffoldArgs) -> Result<()> {
+    let cache = match (&args.cache_dir, args.no_cache) {
+        (_, true) => None,
+        (Some(dir), false) => Some(dir.clone()),
+        (None, false) => IssuerCache::default_dir(),
+    };
+    let opts = ScaffoldOptions {
+        proxy: LazyProxy::new(args.proxy.as_deref()),
+        cache: cache.map(IssuerCache::new),
+        local: match &args.intermediates_dir {
+            Some(dir) => load_issuer_dir(dir)?,
+            None => Vec::new(),
+        },
+        offline: args.offline,
+        bundled: match &args.intermediates_file {
+            Some(path) => load_certificates(path, None)?.1,
+            None => Vec::new(),
+        },
+        // Only read if AIA leaves a gap or --root-from-trust-store asks
+        trust: Some(LazyTrustStore::new(TrustOptions::default())),
+    };
+    let key = match &args.key {
+        Some(path) => Some(load_private_key(path, args.key_password.as_deref())?),
+        None => None,
+    };
 
-    // Evaluate bundle consistency (unrelated, incomplete, self-verify) and system trust
-    // Unused/orphan certs in the bundle
-    if !unused.is_empty() {
-        let labels = unused
-            .iter()
-            .map(|c| subject_cn(c).map(|cn| format!("CN={}", cn)).unwrap_or_else(|| "<unknown>".to_string()))
-            .collect::<Vec<_>>()
-            .join(", ");
-        issues.push(format!("bundle contains unrelated certificate(s): {}", labels));
-    }
-
-    if let Some(last) = seq.last() {
-        let subj = last.subject_name().to_der().unwrap_or_default();
-        let iss = last.issuer_name().to_der().unwrap_or_default();
-        if subj != iss {
-            let label = issuer_cn(last).map(|cn| format!("CN={}", cn)).unwrap_or_else(|| "<unknown>".to_string());
-            issues.push(format!("chain incomplete: missing issuer {}", label));
-        } else if let Ok(pk) = last.public_key() {
-            if last.verify(&pk).is_err() {
-                issues.push("root certificate signature does not verify itself".to_string());
+    let (original, mut bundle) = match (&args.repair, &args.input) {
+        (Some(path), _) => {
+            let (format, certs) = load_certificates(path, None)?;
+            if args.in_place {
+                check_in_place(path, format, args)?;
             }
+            let bundle = repair_bundle(&certs, &opts)?;
+            (Some(certs), bundle)
+        }
+        (None, Some(input)) => (None, build_bundle_from_leaf_file(input, &opts)?),
+        (None, None) => unreachable!("clap requires --input or --repair"),
+    };
+    if let Some(key) = &key {
+        let check = check_key(&bundle.chain[0], key);
+        if let Some(issue) = key_issues(&check).pop() {
+            return Err(Failure::Input(issue.message).into());
         }
     }
-
-    if let Some(leaf) = seq.first() {
-        match validate_chain(leaf, &seq[1..]) {
-            Ok(Ok(())) => {
-                if issues.is_empty() {
-                    stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
-                    write!(&mut stdout, "✅ the chain is valid")?;
-                    stdout.reset()?;
-                    writeln!(&mut stdout)?;
-                } else {
-                    stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
-                    print_bold(&mut stdout, "❌ the chain has issues:")?;
-                    writeln!(&mut stdout)?;
-                    for i in &issues { writeln!(&mut stdout, "- {}", i)?; }
-                }
-            }
-            Ok(Err(msg)) => {
-                issues.push(msg);
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
-                print_bold(&mut stdout, "❌ the chain has issues:")?;
-                writeln!(&mut stdout)?;
-                for i in &issues { writeln!(&mut stdout, "- {}", i)?; }
+    if let Some(trust) = opts.trust.as_ref().filter(|_| args.root_from_trust_store) {
+        add_trust_anchor(&mut bundle, trust.get()?)?;
+    }
+    if args.exclude_root {
+        exclude_root(&mut bundle);
+    }
+    match &original {
+        Some(original) => {
+            for change in diff_bundles(original, &bundle) {
+                println!("{}", change);
             }
-            Err(e) => {
-                issues.push(format!("validation error: {}", e));
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
-                print_bold(&mut stdout, "❌ the chain has issues:")?;
-                writeln!(&mut stdout)?;
-                for i in &issues { writeln!(&mut stdout, "- {}", i)?; }
+        }
+        None => {
+            for (i, (cert, source)) in bundle.chain.iter().zip(&bundle.sources).enumerate() {
+                println!("[{}] {} ({})", i, cn_label(cert), source);
             }
         }
-    } else {
-        stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
-        print_bold(&mut stdout, "❌ the chain has issues:")?;
-        writeln!(&mut stdout)?;
-        writeln!(&mut stdout, "- no certificates parsed")?;
+    }
+    for note in &bundle.notes {
+        println!("note: {}", note);
     }
 
+    let export = ExportOptions {
+        format: args.format,
+        split: args.split,
+        key,
+        p12_password: args.p12_password.clone().unwrap_or_default(),
+    };
+    let output = match (args.in_place, &args.repair, &args.output) {
+        (true, Some(bundle), _) => {
+            let backup = back_up(bundle)?;
+            println!("kept the original bundle as {}", backup.display());
+            bundle.clone()
+        }
+        (false, _, Some(output)) => output.clone(),
+        _ => return Err(Failure::Input("pass --output, or --in-place with --repair".to_string()).into()),
+    };
+    for (path, count) in write_bundle(&output, &bundle.chain, &export)? {
+        println!("wrote {} certificate(s) to {}", count, path.display());
+    }
     Ok(())
 }
 
-// Scaffold subcommand: acknowledge input and output; implementation to follow.
-fn run_scaffold(args: &crate::cli::ScaffoldArgs) -> Result<()> {
-    let chain = build_bundle_from_leaf_file(&args.input)?;
-    write_pem_bundle(&args.output, &chain)?;
-    println!("wrote {} certificate(s) to {}", chain.len(), args.output.display());
+// `--in-place` writes one file of the kind it replaces, so the `.bak` copy
+// and the repaired bundle stay interchangeable.
+fn check_in_place(path: &Path, format: InputFormat, args: &ScaffoldArgs) -> Result<()> {
+    let same = match format {
+        InputFormat::Pem => BundleFormat::Pem,
+        InputFormat::Pkcs7Der => BundleFormat::P7b,
+        InputFormat::Pkcs12 => BundleFormat::P12,
+        // One certificate per DER file, and PKCS#7 is only written as DER
+        InputFormat::Der | InputFormat::Pkcs7Pem => {
+            let msg = format!("--in-place cannot rewrite {} ({}); use --output", path.display(), format_label(format));
+            return Err(Failure::Input(msg).into());
+        }
+    };
+    if args.format != same {
+        let msg = format!("{} is {}: --in-place keeps its format, use --output to convert it", path.display(), format_label(format));
+        return Err(Failure::Input(msg).into());
+    }
     Ok(())
 }
 
+// Copy `path` to `<path>.bak` before it is rewritten in place.
+fn back_up(path: &Path) -> Result<PathBuf> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    let backup = PathBuf::from(name);
+    fs::copy(path, &backup).with_context(|| format!("failed to back up {} to {}", path.display(), backup.display()))?;
+    Ok(backup)
+}
+
 #[cfg(test)]
 mod tests;
 
@@ -0,0 +1,98 @@
+use crate::ffi;
+use crate::report::{Issue, Negotiation};
+use crate::util::ec_curve_name;
+use openssl::error::ErrorStack;
+use openssl::nid::Nid;
+use openssl::pkey::Id as KeyId;
+use openssl::ssl::{NameType, SslConnector, SslConnectorBuilder, SslMethod, SslRef, SslVersion};
+
+// What the handshake settled on, and which of it is considered weak.
+
+/// ALPN protocols offered in the ClientHello, wire-encoded.
+pub const ALPN_PROTOCOLS: &[u8] = b"\x02h2\x08http/1.1";
+
+/// Client context of the verified `diag` session: OpenSSL's default security
+/// level and protocol floor, so what gets verified is never a weak session.
+pub fn connector_builder() -> Result<SslConnectorBuilder, ErrorStack> {
+    SslConnector::builder(SslMethod::tls())
+}
+
+/// Client context for probes (`scan`, and finding out why `diag` could not
+/// connect). Legacy servers may complete the handshake (TLS 1.0/1.1 need
+/// security level 0), so nothing verified should run over it.
+pub fn legacy_connector_builder() -> Result<SslConnectorBuilder, ErrorStack> {
+    let mut builder = SslConnector::builder(SslMethod::tls())?;
+    builder.set_security_level(0);
+    builder.set_min_proto_version(Some(SslVersion::TLS1))?;
+    Ok(builder)
+}
+
+/// Read the negotiated parameters off a completed handshake.
+pub fn negotiation(ssl: &SslRef) -> Negotiation {
+    let cipher = ssl.current_cipher();
+    Negotiation {
+        sni: ssl.servername(NameType::HOST_NAME).map(str::to_string),
+        protocol: ssl.version_str().to_string(),
+        cipher: cipher.map(|c| c.standard_name().unwrap_or(c.name()).to_string()),
+        key_exchange: ssl.peer_tmp_key().ok().map(|key| match key.id() {
+            KeyId::EC => ec_curve_name(&key).unwrap_or_else(|| "EC".to_string()),
+            KeyId::X25519 => "X25519".to_string(),
+            KeyId::X448 => "X448".to_string(),
+            KeyId::DH => format!("DH ({} bits)", key.bits()),
+            other => format!("{:?}", other),
+        }),
+        alpn: ssl.selected_alpn_protocol().map(|p| String::from_utf8_lossy(p).into_owned()),
+        session_reused: ssl.session_reused(),
+        signature_algorithm: peer_signature(ssl),
+    }
+}
+
+/// Warnings for deprecated protocol versions, CBC suites and static RSA key exchange.
+pub fn negotiation_issues(n: &Negotiation) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if is_legacy_protocol(&n.protocol) {
+        issues.push(Issue::warning(format!("server negotiated deprecated protocol {}", n.protocol)));
+    }
+    if let Some(cipher) = &n.cipher {
+        if is_cbc_suite(cipher) {
+            issues.push(Issue::warning(format!("server negotiated CBC cipher suite {}", cipher)));
+        }
+        if is_rsa_key_exchange(cipher) {
+            issues.push(Issue::warning(format!("server negotiated RSA key exchange (no forward secrecy): {}", cipher)));
+        }
+    }
+    issues
+}
+
+/// SSL 3.0, TLS 1.0 and TLS 1.1 (RFC 8996), by OpenSSL version string.
+pub fn is_legacy_protocol(protocol: &str) -> bool {
+    matches!(protocol, "SSLv3" | "TLSv1" | "TLSv1.1")
+}
+
+/// Suites are matched by IANA name.
+pub fn is_cbc_suite(cipher: &str) -> bool {
+    cipher.contains("_CBC_")
+}
+
+/// TLS 1.2 and earlier name the key exchange first; TLS 1.3 suites never use RSA.
+pub fn is_rsa_key_exchange(cipher: &str) -> bool {
+    cipher.starts_with("TLS_RSA_")
+}
+
+// Signature scheme of the server's handshake signature, e.g. "RSA-PSS with SHA256".
+fn peer_signature(ssl: &SslRef) -> Option<String> {
+    let type_nid = ffi::peer_signature_type(ssl)?.as_raw();
+    let scheme = match type_nid {
+        openssl_sys::NID_rsaEncryption => "RSA".to_string(),
+        openssl_sys::NID_rsassaPss => "RSA-PSS".to_string(),
+        openssl_sys::NID_X9_62_id_ecPublicKey => "ECDSA".to_string(),
+        // EdDSA has no separate hash
+        openssl_sys::NID_ED25519 => return Some("Ed25519".to_string()),
+        openssl_sys::NID_ED448 => return Some("Ed448".to_string()),
+        other => Nid::from_raw(other).short_name().map(str::to_string).unwrap_or_else(|_| format!("NID({})", other)),
+    };
+    match ffi::peer_signature_digest(ssl).map(|nid| nid.short_name()) {
+        Some(Ok(hash)) => Some(format!("{} with {}", scheme, hash.to_uppercase())),
+        _ => Some(scheme),
+    }
+}
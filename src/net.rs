@@ -0,0 +1,230 @@
+use anyhow::Result;
+use crate::exit::Failure;
+use openssl::error::ErrorStack;
+use openssl::ssl::{self, HandshakeError};
+use std::ffi::c_int;
+use std::io;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+// TCP setup for `diag`: resolution filtered by address family, bounded
+// connect and handshake, retries of transient failures, and one Failure
+// variant per way a connection can go wrong.
+
+/// Pause between attempts when retrying.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    fn matches(self, addr: &SocketAddr) -> bool {
+        match self {
+            Family::V4 => addr.is_ipv4(),
+            Family::V6 => addr.is_ipv6(),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Family::V4 => "IPv4",
+            Family::V6 => "IPv6",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NetOptions {
+    /// Bound on each TCP connect attempt (`--connect-timeout`).
+    pub connect_timeout: Duration,
+    /// Bound on each read/write of the STARTTLS dialogue and TLS handshake
+    /// (`--handshake-timeout`).
+    pub handshake_timeout: Duration,
+    /// Extra attempts after a refused or timed out connection (`--retries`).
+    pub retries: u32,
+    /// Only use addresses of this family (`-4`/`-6`).
+    pub family: Option<Family>,
+}
+
+impl Default for NetOptions {
+    fn default() -> Self {
+        NetOptions {
+            connect_timeout: Duration::from_secs(10),
+            handshake_timeout: Duration::from_secs(10),
+            retries: 0,
+            family: None,
+        }
+    }
+}
+
+/// Every distinct address `target` resolves to, in resolver order, keeping
+/// only `family` when set. `name` is the host as shown in errors.
+pub fn resolve(target: impl ToSocketAddrs, name: &str, family: Option<Family>) -> Result<Vec<SocketAddr>> {
+    let resolved = target
+        .to_socket_addrs()
+        .map_err(|e| Failure::Dns(format!("failed to resolve {}: {}", name, e)))?;
+    let mut addrs: Vec<SocketAddr> = Vec::new();
+    for addr in resolved {
+        if family.is_none_or(|f| f.matches(&addr)) && !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    if addrs.is_empty() {
+        let what = family.map_or("address", Family::label);
+        return Err(Failure::Dns(format!("{} has no {} address", name, what)).into());
+    }
+    Ok(addrs)
+}
+
+/// Connect to the first address of `addr` (`host:port`) that accepts, with
+/// the handshake timeout already set on the stream.
+pub fn connect(addr: &str, net: &NetOptions) -> Result<TcpStream> {
+    let mut last = None;
+    for candidate in resolve(addr, addr, net.family)? {
+        match TcpStream::connect_timeout(&candidate, net.connect_timeout) {
+            Ok(tcp) => {
+                tcp.set_read_timeout(Some(net.handshake_timeout))?;
+                tcp.set_write_timeout(Some(net.handshake_timeout))?;
+                return Ok(tcp);
+            }
+            Err(e) => last = Some((candidate, e)),
+        }
+    }
+    let (candidate, e) = last.expect("resolve returns at least one address");
+    Err(match e.kind() {
+        io::ErrorKind::ConnectionRefused => Failure::Refused(format!("connection to {} refused", candidate)),
+        kind if is_timeout(kind) => Failure::Timeout(format!(
+            "connection to {} timed out after {}",
+            candidate,
+            seconds(net.connect_timeout)
+        )),
+        _ => Failure::Connection(format!("failed to connect to {}: {}", candidate, e)),
+    }
+    .into())
+}
+
+/// Run `attempt` up to `retries` more times while it fails with a refused
+/// or timed out connection; other failures are returned at once.
+pub fn with_retries<T>(retries: u32, mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut left = retries;
+    loop {
+        match attempt() {
+            Err(e) if left > 0 && is_transient(&e) => {
+                left -= 1;
+                thread::sleep(RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Classify a failed handshake with `addr`: timeout, alert sent by the
+/// server, or any other failure (including our own verification).
+pub fn handshake_failure(addr: &str, net: &NetOptions, err: HandshakeError<TcpStream>) -> anyhow::Error {
+    let timeout = || Failure::Timeout(format!("TLS handshake with {} timed out after {}", addr, seconds(net.handshake_timeout)));
+    match err {
+        HandshakeError::SetupFailure(stack) => stack.into(),
+        // A read timeout on a blocking socket surfaces as EAGAIN
+        HandshakeError::WouldBlock(_) => timeout().into(),
+        HandshakeError::Failure(mid) => ssl_failure(addr, net, mid.into_error()),
+    }
+}
+
+/// Classify an error of the TLS session with `addr`, as for the handshake.
+pub fn ssl_failure(addr: &str, net: &NetOptions, err: ssl::Error) -> anyhow::Error {
+    if err.io_error().is_some_and(|e| is_timeout(e.kind())) {
+        let msg = format!("TLS handshake with {} timed out after {}", addr, seconds(net.handshake_timeout));
+        return Failure::Timeout(msg).into();
+    }
+    if let Some(alert) = err.ssl_error().and_then(received_alert) {
+        let name = alert_name(alert).map_or_else(|| format!("alert {}", alert), |n| format!("{} ({})", n, alert));
+        return Failure::TlsAlert(format!("{} aborted the TLS handshake with alert {}", addr, name)).into();
+    }
+    anyhow::Error::new(err).context(Failure::Connection(format!("TLS handshake with {} failed", addr)))
+}
+
+/// Whether a handshake failed on something our default security level
+/// refuses from a legacy server: its protocol version, or the SHA-1/MD5
+/// handshake signature of TLS 1.0/1.1.
+pub fn is_legacy_refusal(err: &anyhow::Error) -> bool {
+    const SSL_R_UNSUPPORTED_PROTOCOL: c_int = 258;
+    const SSL_R_WRONG_VERSION_NUMBER: c_int = 267;
+    const SSL_R_LEGACY_SIGALG_DISALLOWED_OR_UNSUPPORTED: c_int = 333;
+    err.chain()
+        .filter_map(|e| e.downcast_ref::<ssl::Error>())
+        .filter_map(ssl::Error::ssl_error)
+        .flat_map(ErrorStack::errors)
+        .any(|e| {
+            matches!(
+                e.reason_code(),
+                SSL_R_UNSUPPORTED_PROTOCOL | SSL_R_WRONG_VERSION_NUMBER | SSL_R_LEGACY_SIGALG_DISALLOWED_OR_UNSUPPORTED
+            )
+        })
+}
+
+fn is_transient(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<Failure>(), Some(Failure::Refused(_) | Failure::Timeout(_)))
+}
+
+/// Whether an I/O error is a socket timeout (EAGAIN on Unix, ETIMEDOUT on Windows).
+pub fn is_timeout(kind: io::ErrorKind) -> bool {
+    matches!(kind, io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock)
+}
+
+fn seconds(d: Duration) -> String {
+    format!("{}s", d.as_secs_f64())
+}
+
+// OpenSSL records an alert received from the peer as "SSL alert number N"
+// in the error data.
+fn received_alert(stack: &ErrorStack) -> Option<u8> {
+    stack
+        .errors()
+        .iter()
+        .find_map(|e| e.data()?.strip_prefix("SSL alert number ")?.trim().parse().ok())
+}
+
+/// RFC 8446 section 6 (and earlier) alert descriptions.
+fn alert_name(code: u8) -> Option<&'static str> {
+    Some(match code {
+        0 => "close_notify",
+        10 => "unexpected_message",
+        20 => "bad_record_mac",
+        21 => "decryption_failed",
+        22 => "record_overflow",
+        30 => "decompression_failure",
+        40 => "handshake_failure",
+        41 => "no_certificate",
+        42 => "bad_certificate",
+        43 => "unsupported_certificate",
+        44 => "certificate_revoked",
+        45 => "certificate_expired",
+        46 => "certificate_unknown",
+        47 => "illegal_parameter",
+        48 => "unknown_ca",
+        49 => "access_denied",
+        50 => "decode_error",
+        51 => "decrypt_error",
+        60 => "export_restriction",
+        70 => "protocol_version",
+        71 => "insufficient_security",
+        80 => "internal_error",
+        86 => "inappropriate_fallback",
+        90 => "user_canceled",
+        100 => "no_renegotiation",
+        109 => "missing_extension",
+        110 => "unsupported_extension",
+        111 => "certificate_unobtainable",
+        112 => "unrecognized_name",
+        113 => "bad_certificate_status_response",
+        114 => "bad_certificate_hash_value",
+        115 => "unknown_psk_identity",
+        116 => "certificate_required",
+        120 => "no_application_protocol",
+        _ => return None,
+    })
+}
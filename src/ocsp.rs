@@ -0,0 +1,210 @@
+use anyhow::{bail, Context, Result};
+use crate::extensions::aia_ocsp_urls;
+use crate::ffi;
+use crate::report::{Issue, OcspCheck, RevocationStatus, StapleCheck, StapleState};
+use crate::validate::TrustStore;
+use openssl::hash::MessageDigest;
+use openssl::ocsp::{OcspBasicResponseRef, OcspCertId, OcspCertIdRef, OcspCertStatus, OcspFlag, OcspRequest, OcspResponse, OcspResponseStatus};
+use openssl::stack::Stack;
+use openssl::x509::{X509, X509Ref};
+use reqwest::blocking::Client;
+
+// OCSP revocation checking (RFC 6960) for the leaf of an ordered chain.
+
+/// Clock skew tolerated when checking thisUpdate/nextUpdate, in seconds.
+const VALIDITY_SLACK_SECS: i64 = 300;
+
+/// Ask the leaf's OCSP responder (AIA) about the leaf, using the next
+/// certificate in `seq` as issuer. Failures are reported in the result.
+pub fn check_ocsp(client: &Client, seq: &[&X509Ref], trust: &TrustStore) -> OcspCheck {
+    let Some(leaf) = seq.first() else { return OcspCheck::failed(None, "no certificates parsed") };
+    let Some(issuer) = seq.get(1) else {
+        return OcspCheck::failed(None, "issuer of the leaf is not in the chain");
+    };
+    let urls = aia_ocsp_urls(leaf);
+    if urls.is_empty() {
+        return OcspCheck::failed(None, "leaf has no OCSP responder URL (AIA)");
+    }
+
+    let mut last_err = None;
+    for url in urls {
+        let result = query_responder(client, &url, leaf, issuer)
+            .and_then(|der| evaluate_response(&der, leaf, issuer, trust));
+        match result {
+            Ok(Some(mut check)) => {
+                check.responder = Some(url);
+                return check;
+            }
+            Ok(None) => {
+                last_err = Some(OcspCheck::failed(Some(url), "OCSP response does not cover the leaf certificate"))
+            }
+            Err(e) => last_err = Some(OcspCheck::failed(Some(url), format!("{:#}", e))),
+        }
+    }
+    last_err.expect("at least one responder URL was tried")
+}
+
+fn query_responder(client: &Client, url: &str, leaf: &X509Ref, issuer: &X509Ref) -> Result<Vec<u8>> {
+    let mut req = OcspRequest::new()?;
+    req.add_id(OcspCertId::from_cert(MessageDigest::sha1(), leaf, issuer)?)?;
+    let resp = client
+        .post(url)
+        .header("Content-Type", "application/ocsp-request")
+        .body(req.to_der()?)
+        .send()
+        .with_context(|| format!("POST {} failed", url))?;
+    if !resp.status().is_success() {
+        bail!("{}: HTTP {}", url, resp.status());
+    }
+    Ok(resp.bytes()?.to_vec())
+}
+
+/// Parse a DER OCSPResponse, verify its signature (issuer-signed or delegated
+/// responder chaining to `trust`) and extract the leaf's status.
+/// Returns Ok(None) when the response is valid but says nothing about the leaf.
+pub fn evaluate_response(
+    der: &[u8],
+    leaf: &X509Ref,
+    issuer: &X509Ref,
+    trust: &TrustStore,
+) -> Result<Option<OcspCheck>> {
+    let resp = OcspResponse::from_der(der).context("malformed OCSP response")?;
+    if resp.status() != OcspResponseStatus::SUCCESSFUL {
+        bail!("responder returned {}", response_status_name(resp.status()));
+    }
+    let basic = resp.basic().context("OCSP response has no basic response")?;
+
+    // Only the leaf's issuer is trusted without a chain (TRUST_OTHER): any
+    // other signer, even one the server sent, is a delegated responder that
+    // has to chain to the trust store and carry the OCSP signing EKU.
+    let mut certs: Stack<X509> = Stack::new()?;
+    certs.push(issuer.to_owned())?;
+    basic
+        .verify(&certs, trust.store(), OcspFlag::TRUST_OTHER)
+        .context("OCSP response signature does not verify")?;
+
+    let id = OcspCertId::from_cert(MessageDigest::sha1(), leaf, issuer)?;
+    Ok(find_status(&basic, &id))
+}
+
+/// Classify the OCSP response stapled in the handshake (status_request).
+pub fn check_staple(staple: Option<&[u8]>, seq: &[&X509Ref], trust: &TrustStore) -> StapleCheck {
+    let Some(der) = staple.filter(|d| !d.is_empty()) else {
+        return StapleCheck { state: StapleState::NoStaple, response: None, error: None };
+    };
+    let (Some(leaf), Some(issuer)) = (seq.first(), seq.get(1)) else {
+        return StapleCheck {
+            state: StapleState::Invalid,
+            response: None,
+            error: Some("issuer of the leaf is not in the chain".to_string()),
+        };
+    };
+    match evaluate_response(der, leaf, issuer, trust) {
+        Ok(Some(check)) => {
+            let state = match check.status {
+                RevocationStatus::Revoked => StapleState::Revoked,
+                _ if !check.current => StapleState::Expired,
+                RevocationStatus::Good => StapleState::Good,
+                _ => StapleState::Unknown,
+            };
+            StapleCheck { state, response: Some(check), error: None }
+        }
+        Ok(None) => StapleCheck { state: StapleState::WrongCertificate, response: None, error: None },
+        Err(e) => StapleCheck { state: StapleState::Invalid, response: None, error: Some(format!("{:#}", e)) },
+    }
+}
+
+/// Issue lines for a stapled response. Not stapling at all is not an issue.
+pub fn staple_issues(check: &StapleCheck) -> Vec<Issue> {
+    let next_update = || {
+        check.response.as_ref().and_then(|r| r.next_update.clone()).unwrap_or_else(|| "-".to_string())
+    };
+    match check.state {
+        StapleState::NoStaple | StapleState::Good => vec![],
+        StapleState::Revoked => vec![Issue::error(format!(
+            "stapled OCSP response reports the leaf as revoked: {}",
+            check.response.as_ref().and_then(|r| r.reason.as_deref()).unwrap_or("no reason given")
+        ))],
+        StapleState::Expired => vec![Issue::warning(format!("stapled OCSP response expired (next update {})", next_update()))],
+        StapleState::Unknown => vec![Issue::warning("stapled OCSP response reports the leaf status as unknown")],
+        StapleState::WrongCertificate => vec![Issue::error("stapled OCSP response is for a different certificate")],
+        StapleState::Invalid => vec![Issue::error(format!(
+            "stapled OCSP response is invalid: {}",
+            check.error.as_deref().unwrap_or("unknown error")
+        ))],
+    }
+}
+
+/// Human issue lines for an OCSP result.
+pub fn ocsp_issues(check: &OcspCheck) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    match check.status {
+        RevocationStatus::Good => {}
+        RevocationStatus::Revoked => issues.push(Issue::error(format!(
+            "leaf certificate is revoked (OCSP): {}{}",
+            check.reason.as_deref().unwrap_or("no reason given"),
+            check.revocation_time.as_ref().map(|t| format!(" since {}", t)).unwrap_or_default()
+        ))),
+        RevocationStatus::Unknown => issues.push(Issue::warning("OCSP responder does not know the leaf certificate")),
+        RevocationStatus::Error => issues.push(Issue::warning(format!(
+            "OCSP check failed: {}",
+            check.error.as_deref().unwrap_or("unknown error")
+        ))),
+    }
+    if check.status != RevocationStatus::Error && !check.current {
+        issues.push(Issue::warning(format!(
+            "OCSP response is outside its validity window (this update {}, next update {})",
+            check.this_update.as_deref().unwrap_or("-"),
+            check.next_update.as_deref().unwrap_or("-")
+        )));
+    }
+    issues
+}
+
+/// CRLReason names (RFC 5280 section 5.3.1); shared with CRL checking.
+pub fn revocation_reason_name(code: i32) -> Option<&'static str> {
+    Some(match code {
+        0 => "unspecified",
+        1 => "keyCompromise",
+        2 => "cACompromise",
+        3 => "affiliationChanged",
+        4 => "superseded",
+        5 => "cessationOfOperation",
+        6 => "certificateHold",
+        8 => "removeFromCRL",
+        9 => "privilegeWithdrawn",
+        10 => "aACompromise",
+        _ => return None,
+    })
+}
+
+fn response_status_name(status: OcspResponseStatus) -> String {
+    match status {
+        OcspResponseStatus::MALFORMED_REQUEST => "malformedRequest".to_string(),
+        OcspResponseStatus::INTERNAL_ERROR => "internalError".to_string(),
+        OcspResponseStatus::TRY_LATER => "tryLater".to_string(),
+        OcspResponseStatus::SIG_REQUIRED => "sigRequired".to_string(),
+        OcspResponseStatus::UNAUTHORIZED => "unauthorized".to_string(),
+        other => format!("status {}", other.as_raw()),
+    }
+}
+
+// The SingleResponse for `id`, if the response has one.
+fn find_status(basic: &OcspBasicResponseRef, id: &OcspCertIdRef) -> Option<OcspCheck> {
+    let single = ffi::find_status(basic, id)?;
+    let status = match single.status {
+        OcspCertStatus::GOOD => RevocationStatus::Good,
+        OcspCertStatus::REVOKED => RevocationStatus::Revoked,
+        _ => RevocationStatus::Unknown,
+    };
+    Some(OcspCheck {
+        responder: None,
+        status,
+        reason: revocation_reason_name(single.reason.as_raw()).map(str::to_string),
+        revocation_time: single.revocation_time.map(|t| t.to_string()),
+        this_update: single.this_update.map(|t| t.to_string()),
+        next_update: single.next_update.map(|t| t.to_string()),
+        current: single.is_current(VALIDITY_SLACK_SECS),
+        error: None,
+    })
+}
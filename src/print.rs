@@ -1,7 +1,16 @@
 use anyhow::Result;
 use openssl::x509::X509Ref;
+use crate::extensions::parse_extensions;
+use crate::report::{
+    AddressCheck, AnchorSource, Grade, CertInfo, ClientAuth, KeyCheck, CrlCheck, DiagReport, ExpiryStatus, Extensions, NameAttr, Negotiation, OcspCheck, PublicKeyInfo, RevocationStatus,
+    ScanReport, SctCheck, StapleCheck, StapleState, Status, Verdict,
+};
+use crate::input::format_label;
+use crate::sct::source_label;
+use crate::validate::{expiry_status, ExpiryThresholds};
 use crate::util::{name_items, fingerprint_sha256, ec_curve_name, infer_cert_type, format_asn1_time};
 use openssl::pkey::Id as KeyId;
+use serde::Serialize;
 use std::io::Write;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
@@ -19,71 +28,422 @@ pub fn print_bold_blue<W: WriteColor>(w: &mut W, text: &str) -> Result<()> {
     Ok(())
 }
 
-// Render the ordered chain with a simple "is issued by ->" separator for readability.
-pub fn print_chain_with_separator(seq: &[&X509Ref]) -> Result<()> {
+// Collect a concise, human-oriented view: Subject/Issuer (selected attributes),
+// validity, key algorithm and size, and a SHA-256 fingerprint.
+pub fn cert_info(idx: usize, cert: &X509Ref, thresholds: &ExpiryThresholds) -> Result<CertInfo> {
+    let to_attrs = |items: Vec<(&'static str, String)>| {
+        items
+            .into_iter()
+            .map(|(label, value)| NameAttr { label: label.to_string(), value })
+            .collect::<Vec<_>>()
+    };
+
+    let pkey = cert.public_key()?;
+    let algorithm = match pkey.id() {
+        KeyId::RSA => "RSA".to_string(),
+        KeyId::EC => format!("EC{}", ec_curve_name(&pkey).map(|c| format!(" ({})", c)).unwrap_or_default()),
+        KeyId::ED25519 => "Ed25519".to_string(),
+        KeyId::ED448 => "Ed448".to_string(),
+        KeyId::X25519 => "X25519".to_string(),
+        KeyId::X448 => "X448".to_string(),
+        other => format!("{:?}", other),
+    };
+
+    let (days_remaining, expiry) = expiry_status(cert, thresholds);
+
+    Ok(CertInfo {
+        index: idx,
+        cert_type: infer_cert_type(cert).map(str::to_string),
+        subject: to_attrs(name_items(cert.subject_name().entries())),
+        issuer: to_attrs(name_items(cert.issuer_name().entries())),
+        not_before: format_asn1_time(cert.not_before()),
+        not_after: format_asn1_time(cert.not_after()),
+        days_remaining,
+        expiry,
+        public_key: PublicKeyInfo { algorithm, bits: pkey.bits() },
+        // Prefer SHA-256 which is widely used by modern tooling
+        fingerprint_sha256: fingerprint_sha256(cert)?,
+        extensions: parse_extensions(cert),
+    })
+}
+
+/// Render the whole report for a terminal: the ordered chain with a simple
+/// "is issued by ->" separator, unrelated certificates, then the verdict.
+/// `verbose` adds the less common extensions to each certificate.
+pub fn print_report(report: &DiagReport, verbose: bool) -> Result<()> {
     let mut stdout = StandardStream::stdout(ColorChoice::Auto);
-    for (i, cert) in seq.iter().enumerate() {
-        print_cert_info_to(&mut stdout, i + 1, cert)?;
-        if i + 1 < seq.len() {
+    if let Some(format) = report.input_format {
+        writeln!(&mut stdout, "Read {} as {}", report.source, format_label(format))?;
+    }
+    writeln!(&mut stdout, "--- Certificate chain (leaf -> root) ---")?;
+    for (i, info) in report.chain.iter().enumerate() {
+        print_cert_info_to(&mut stdout, info, verbose)?;
+        if i + 1 < report.chain.len() {
             writeln!(&mut stdout, "is issued by ->")?;
         }
     }
+    // Unrelated certificates are listed without "issued by"
+    for info in &report.unused {
+        print_cert_info_to(&mut stdout, info, verbose)?;
+    }
+    // A mismatch is reported as an issue with the verdict
+    if let Some(check) = &report.hostname {
+        if let Some(entry) = &check.matched_entry {
+            print_bold(&mut stdout, "Hostname:")?;
+            write!(&mut stdout, " ")?;
+            print_bold_blue(&mut stdout, &check.name)?;
+            writeln!(&mut stdout, " matched by {}", entry)?;
+        }
+    }
+    if let Some(check) = &report.ocsp {
+        print_ocsp_to(&mut stdout, check)?;
+    }
+    if let Some(staple) = &report.staple {
+        print_staple_to(&mut stdout, staple)?;
+    }
+    if let Some(checks) = &report.crl {
+        print_crl_to(&mut stdout, checks)?;
+    }
+    if let Some(check) = &report.sct {
+        print_sct_to(&mut stdout, check)?;
+    }
+    if let Some(n) = &report.negotiation {
+        print_negotiation_to(&mut stdout, n)?;
+    }
+    if let Some(checks) = &report.addresses {
+        print_addresses_to(&mut stdout, checks)?;
+    }
+    if let Some(check) = &report.key {
+        print_key_to(&mut stdout, check)?;
+    }
+    // Only when mutual TLS is in play
+    if let Some(auth) = report.client_auth.as_ref().filter(|a| a.requested || a.presented.is_some()) {
+        print_client_auth_to(&mut stdout, auth)?;
+    }
+    print_verdict_to(&mut stdout, &report.verdict)
+}
+
+fn print_crl_to<W: WriteColor>(w: &mut W, checks: &[CrlCheck]) -> Result<()> {
+    print_bold(w, "CRL:")?;
+    writeln!(w)?;
+    for c in checks {
+        let (color, label) = match c.status {
+            RevocationStatus::Good if c.stale => (Color::Yellow, "good (stale CRL)"),
+            RevocationStatus::Good => (Color::Green, "good"),
+            RevocationStatus::Revoked => (Color::Red, "revoked"),
+            RevocationStatus::Unknown => (Color::Yellow, "unknown"),
+            RevocationStatus::Error => (Color::Yellow, "not checked"),
+        };
+        write!(w, "    - [{}] {}: ", c.index, c.subject)?;
+        w.set_color(ColorSpec::new().set_bold(true).set_fg(Some(color)))?;
+        write!(w, "{}", label)?;
+        w.reset()?;
+        if let Some(reason) = &c.reason {
+            write!(w, " ({})", reason)?;
+        }
+        match (&c.source, &c.error) {
+            (_, Some(err)) => writeln!(w, " - {}", err)?,
+            (Some(src), None) => writeln!(w, " - {}", src)?,
+            (None, None) => writeln!(w)?,
+        }
+    }
     Ok(())
 }
 
-// Print a concise, human-oriented view: Subject/Issuer (selected attributes),
-// key algorithm and size, and a SHA-256 fingerprint.
-pub fn print_cert_info(idx: usize, cert: &X509Ref) -> Result<()> {
+fn print_addresses_to<W: WriteColor>(w: &mut W, checks: &[AddressCheck]) -> Result<()> {
+    print_bold(w, "Addresses:")?;
+    writeln!(w)?;
+    for c in checks {
+        let (color, label) = match (&c.error, c.different_leaf, &c.verify_error) {
+            (Some(_), _, _) => (Color::Red, "failed"),
+            (None, true, _) => (Color::Red, "different leaf"),
+            (None, false, Some(_)) => (Color::Red, "chain does not verify"),
+            (None, false, None) => (Color::Green, "ok"),
+        };
+        write!(w, "    - {}: ", c.address)?;
+        w.set_color(ColorSpec::new().set_bold(true).set_fg(Some(color)))?;
+        write!(w, "{}", label)?;
+        w.reset()?;
+        match c.chain_fingerprints.first() {
+            Some(leaf) => writeln!(w, " - leaf {}, {} certificate(s)", leaf, c.chain_fingerprints.len())?,
+            None => writeln!(w)?,
+        }
+    }
+    Ok(())
+}
+
+/// Result of `keymatch`.
+pub fn print_key_check(check: &KeyCheck) -> Result<()> {
     let mut stdout = StandardStream::stdout(ColorChoice::Auto);
-    print_cert_info_to(&mut stdout, idx, cert)
+    print_key_to(&mut stdout, check)
 }
 
-fn print_cert_info_to<W: WriteColor>(w: &mut W, idx: usize, cert: &X509Ref) -> Result<()> {
-    let subject_items = name_items(cert.subject_name().entries());
-    let issuer_items = name_items(cert.issuer_name().entries());
+fn print_key_to<W: WriteColor>(w: &mut W, check: &KeyCheck) -> Result<()> {
+    print_bold(w, "Private key:")?;
+    write!(w, " {} ", check.key)?;
+    let (color, label) = if check.matches { (Color::Green, "matches") } else { (Color::Red, "does not match") };
+    w.set_color(ColorSpec::new().set_bold(true).set_fg(Some(color)))?;
+    write!(w, "{}", label)?;
+    w.reset()?;
+    writeln!(w, " the leaf {} ({})", check.leaf, check.leaf_fingerprint)?;
+    Ok(())
+}
 
-    let pkey = cert.public_key()?;
-    let key_bits = pkey.bits();
-    let alg = match pkey.id() {
-        KeyId::RSA => "RSA".to_string(),
-        KeyId::EC => format!("EC{}", ec_curve_name(&pkey).map(|c| format!(" ({})", c)).unwrap_or_default()),
-        KeyId::ED25519 => "Ed25519".to_string(),
-        KeyId::ED448 => "Ed448".to_string(),
-        KeyId::X25519 => "X25519".to_string(),
-        KeyId::X448 => "X448".to_string(),
-        other => format!("{:?}", other),
+fn print_client_auth_to<W: WriteColor>(w: &mut W, auth: &ClientAuth) -> Result<()> {
+    print_bold(w, "Client certificate:")?;
+    writeln!(w)?;
+    writeln!(w, "    Requested by server: {}", if auth.requested { "yes" } else { "no" })?;
+    write!(w, "    Presented: {}", auth.presented.as_deref().unwrap_or("none"))?;
+    if auth.requested && auth.presented_accepted == Some(false) {
+        w.set_color(ColorSpec::new().set_bold(true).set_fg(Some(Color::Yellow)))?;
+        write!(w, " (issuer not in the acceptable CA list)")?;
+        w.reset()?;
+    }
+    writeln!(w)?;
+    if !auth.requested {
+        return Ok(());
+    }
+    if auth.acceptable_cas.is_empty() {
+        writeln!(w, "    Acceptable CAs: any")?;
+    } else {
+        writeln!(w, "    Acceptable CAs:")?;
+        for ca in &auth.acceptable_cas {
+            writeln!(w, "      - {}", ca)?;
+        }
+    }
+    writeln!(w, "    Signature algorithms: {}", auth.signature_algorithms.join(", "))?;
+    Ok(())
+}
+
+fn print_negotiation_to<W: WriteColor>(w: &mut W, n: &Negotiation) -> Result<()> {
+    let none = || "none".to_string();
+    print_bold(w, "Connection:")?;
+    writeln!(w)?;
+    writeln!(w, "    SNI: {}", n.sni.clone().unwrap_or_else(none))?;
+    writeln!(w, "    Protocol: {}", n.protocol)?;
+    writeln!(w, "    Cipher: {}", n.cipher.clone().unwrap_or_else(none))?;
+    writeln!(w, "    Key exchange: {}", n.key_exchange.clone().unwrap_or_else(|| "RSA".to_string()))?;
+    writeln!(w, "    Server signature: {}", n.signature_algorithm.clone().unwrap_or_else(none))?;
+    writeln!(w, "    ALPN: {}", n.alpn.clone().unwrap_or_else(none))?;
+    writeln!(w, "    Session: {}", if n.session_reused { "resumed" } else { "new" })?;
+    Ok(())
+}
+
+fn print_sct_to<W: WriteColor>(w: &mut W, check: &SctCheck) -> Result<()> {
+    print_bold(w, "SCTs:")?;
+    if check.scts.is_empty() {
+        writeln!(w, " none")?;
+        return Ok(());
+    }
+    writeln!(w)?;
+    for s in &check.scts {
+        let (color, label) = match (s.verified, &s.error) {
+            (Some(true), _) => (Color::Green, "verified"),
+            (Some(false), _) => (Color::Red, "bad signature"),
+            (None, Some(_)) => (Color::Yellow, "not checked"),
+            (None, None) => (Color::Yellow, "unknown log"),
+        };
+        writeln!(w, "    - {} SCT from {}", source_label(s.source), s.log.as_deref().unwrap_or(&s.log_id))?;
+        if s.log.is_some() {
+            writeln!(w, "      Log ID: {}", s.log_id)?;
+        }
+        writeln!(w, "      Timestamp: {}", s.timestamp)?;
+        write!(w, "      Signature: {} ", s.signature_algorithm)?;
+        w.set_color(ColorSpec::new().set_bold(true).set_fg(Some(color)))?;
+        write!(w, "{}", label)?;
+        w.reset()?;
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn print_staple_to<W: WriteColor>(w: &mut W, staple: &StapleCheck) -> Result<()> {
+    let (color, label) = match staple.state {
+        StapleState::NoStaple => (Color::Yellow, "no staple"),
+        StapleState::Good => (Color::Green, "staple good"),
+        StapleState::Revoked => (Color::Red, "staple says revoked"),
+        StapleState::Unknown => (Color::Yellow, "staple status unknown"),
+        StapleState::Expired => (Color::Red, "staple expired"),
+        StapleState::WrongCertificate => (Color::Red, "staple for wrong certificate"),
+        StapleState::Invalid => (Color::Red, "staple invalid"),
     };
+    print_bold(w, "OCSP Stapling:")?;
+    write!(w, " ")?;
+    w.set_color(ColorSpec::new().set_bold(true).set_fg(Some(color)))?;
+    write!(w, "{}", label)?;
+    w.reset()?;
+    writeln!(w)?;
+    if let Some(resp) = &staple.response {
+        if let Some(t) = &resp.this_update {
+            writeln!(w, "    - This Update: {}", t)?;
+        }
+        if let Some(t) = &resp.next_update {
+            writeln!(w, "    - Next Update: {}", t)?;
+        }
+    }
+    if let Some(err) = &staple.error {
+        writeln!(w, "    - Error: {}", err)?;
+    }
+    Ok(())
+}
+
+fn print_ocsp_to<W: WriteColor>(w: &mut W, check: &OcspCheck) -> Result<()> {
+    let (color, label) = match check.status {
+        RevocationStatus::Good => (Color::Green, "good"),
+        RevocationStatus::Revoked => (Color::Red, "revoked"),
+        RevocationStatus::Unknown => (Color::Yellow, "unknown"),
+        RevocationStatus::Error => (Color::Yellow, "not checked"),
+    };
+    print_bold(w, "OCSP:")?;
+    write!(w, " ")?;
+    w.set_color(ColorSpec::new().set_bold(true).set_fg(Some(color)))?;
+    write!(w, "{}", label)?;
+    w.reset()?;
+    if let Some(reason) = &check.reason {
+        write!(w, " ({})", reason)?;
+    }
+    writeln!(w)?;
+    if let Some(url) = &check.responder {
+        writeln!(w, "    - Responder: {}", url)?;
+    }
+    if let Some(t) = &check.revocation_time {
+        writeln!(w, "    - Revoked At: {}", t)?;
+    }
+    if let Some(t) = &check.this_update {
+        writeln!(w, "    - This Update: {}", t)?;
+    }
+    if let Some(t) = &check.next_update {
+        writeln!(w, "    - Next Update: {}", t)?;
+    }
+    Ok(())
+}
+
+/// Render a scan: supported versions, then a suite-by-version matrix where
+/// each cell is the suite's rank in the server's order, then the grade.
+pub fn print_scan(report: &ScanReport) -> Result<()> {
+    let mut stdout = StandardStream::stdout(ColorChoice::Auto);
+    let w = &mut stdout;
+    writeln!(w, "--- Protocols ({}) ---", report.source)?;
+    for p in &report.protocols {
+        let order = match p.server_preference {
+            Some(true) => " (server order)",
+            Some(false) => " (client order)",
+            None => "",
+        };
+        writeln!(w, "    {:<8} {}{}", p.protocol, if p.supported { "yes" } else { "no" }, order)?;
+    }
+
+    let mut suites: Vec<&str> = Vec::new();
+    for cipher in report.protocols.iter().flat_map(|p| &p.ciphers) {
+        if !suites.contains(&cipher.as_str()) {
+            suites.push(cipher);
+        }
+    }
+    if !suites.is_empty() {
+        let width = suites.iter().map(|s| s.len()).max().unwrap_or(0);
+        writeln!(w, "--- Cipher suites ---")?;
+        write!(w, "    {:<width$}", "", width = width)?;
+        for p in &report.protocols {
+            write!(w, " {:>7}", p.protocol)?;
+        }
+        writeln!(w)?;
+        for suite in suites {
+            write!(w, "    {:<width$}", suite, width = width)?;
+            for p in &report.protocols {
+                let cell = p.ciphers.iter().position(|c| c == suite).map(|i| (i + 1).to_string());
+                write!(w, " {:>7}", cell.as_deref().unwrap_or("-"))?;
+            }
+            writeln!(w)?;
+        }
+    }
+
+    let color = match report.grade {
+        Grade::A => Color::Green,
+        Grade::B => Color::Yellow,
+        Grade::C | Grade::F => Color::Red,
+    };
+    print_bold(w, "Grade:")?;
+    write!(w, " ")?;
+    w.set_color(ColorSpec::new().set_bold(true).set_fg(Some(color)))?;
+    write!(w, "{:?}", report.grade)?;
+    w.reset()?;
+    writeln!(w)?;
+    for i in &report.issues { writeln!(w, "- {}", i.message)?; }
+    Ok(())
+}
 
-    // Prefer SHA-256 which is widely used by modern tooling
-    let fp = fingerprint_sha256(cert)?;
+/// Serialize the report as pretty-printed JSON on stdout.
+pub fn print_json<T: Serialize>(report: &T) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut lock = stdout.lock();
+    serde_json::to_writer_pretty(&mut lock, report)?;
+    writeln!(lock)?;
+    Ok(())
+}
+
+fn print_verdict_to<W: WriteColor>(w: &mut W, verdict: &Verdict) -> Result<()> {
+    match verdict.status {
+        Status::Valid => {
+            w.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+            write!(w, "✅ the chain is valid")?;
+            w.reset()?;
+            writeln!(w)?;
+        }
+        Status::Warnings => {
+            w.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+            print_bold(w, "⚠️ the chain is valid with warnings:")?;
+            writeln!(w)?;
+        }
+        Status::Invalid => {
+            w.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
+            print_bold(w, "❌ the chain has issues:")?;
+            writeln!(w)?;
+        }
+    }
+    for i in &verdict.issues { writeln!(w, "- {}", i.message)?; }
+    if let Some(anchor) = &verdict.trust_anchor {
+        let source = match anchor.source {
+            AnchorSource::System => "system trust store",
+            AnchorSource::CaFile => "--ca-file",
+            AnchorSource::CaDir => "--ca-dir",
+        };
+        write!(w, "  ")?;
+        print_bold(w, "Trust anchor:")?;
+        write!(w, " ")?;
+        print_bold_blue(w, &anchor.subject)?;
+        writeln!(w, " (from {})", source)?;
+    }
+    Ok(())
+}
 
-    writeln!(w, "[{}]", idx)?;
+fn print_cert_info_to<W: WriteColor>(w: &mut W, info: &CertInfo, verbose: bool) -> Result<()> {
+    writeln!(w, "[{}]", info.index)?;
     
     write!(w, "  ")?;
     print_bold(w, "Subject:")?;
     writeln!(w)?;
 
-    if let Some(kind) = infer_cert_type(cert) {
+    if let Some(kind) = &info.cert_type {
         write!(w, "    - ")?;
         print_bold(w, "Type:")?;
         write!(w, " ")?;
-        print_bold_blue(w, &kind)?;
+        print_bold_blue(w, kind)?;
         writeln!(w)?;
     }
-    for (label, value) in subject_items {
+    for attr in &info.subject {
         write!(w, "    - ")?;
-        print_bold(w, &format!("{}:", label))?;
+        print_bold(w, &format!("{}:", attr.label))?;
         write!(w, " ")?;
-        print_bold_blue(w, &value)?;
+        print_bold_blue(w, &attr.value)?;
         writeln!(w)?;
     }
     print_bold(w, "  Issuer:")?;
     writeln!(w, "  ")?;
-    for (label, value) in issuer_items {
+    for attr in &info.issuer {
         write!(w, "    - ")?;
-        print_bold(w, &format!("{}:", label))?;
+        print_bold(w, &format!("{}:", attr.label))?;
         write!(w, " ")?;
-        print_bold_blue(w, &value)?;
+        print_bold_blue(w, &attr.value)?;
         writeln!(w)?;
     }
     print_bold(w, "  Validity:")?;
@@ -91,24 +451,94 @@ fn print_cert_info_to<W: WriteColor>(w: &mut W, idx: usize, cert: &X509Ref) -> R
     write!(w, "    - ")?;
     print_bold(w, "Not Before:")?;
     write!(w, " ")?;
-    print_bold_blue(w, &format_asn1_time(cert.not_before()))?;
+    print_bold_blue(w, &info.not_before)?;
     writeln!(w)?;
     write!(w, "    - ")?;
     print_bold(w, "Not After: ")?;
     write!(w, " ")?;
-    print_bold_blue(w, &format_asn1_time(cert.not_after()))?;
+    print_bold_blue(w, &info.not_after)?;
     writeln!(w)?;
+    if let Some(days) = info.days_remaining {
+        let (color, text) = match info.expiry {
+            ExpiryStatus::Ok => (Color::Green, format!("{} days remaining", days)),
+            ExpiryStatus::Warning => (Color::Yellow, format!("{} days remaining", days)),
+            ExpiryStatus::Critical => (Color::Red, format!("{} days remaining", days)),
+            ExpiryStatus::Expired => (Color::Red, format!("expired {} days ago", -days)),
+            ExpiryStatus::NotYetValid => (Color::Red, "not yet valid".to_string()),
+        };
+        write!(w, "    - ")?;
+        print_bold(w, "Status:")?;
+        write!(w, " ")?;
+        w.set_color(ColorSpec::new().set_bold(true).set_fg(Some(color)))?;
+        write!(w, "{}", text)?;
+        w.reset()?;
+        writeln!(w)?;
+    }
 
     print_bold(w, "  Public Key:")?;
     write!(w, " ")?;
-    print_bold_blue(w, &format!("{} {} bits", alg, key_bits))?;
+    print_bold_blue(w, &format!("{} {} bits", info.public_key.algorithm, info.public_key.bits))?;
     writeln!(w)?;
 
     print_bold(w, "  SHA-256 Fingerprint:")?;
     write!(w, " ")?;
-    print_bold_blue(w, &fp)?;
+    print_bold_blue(w, &info.fingerprint_sha256)?;
     writeln!(w)?;
+
+    print_extensions_to(w, &info.extensions, verbose)?;
     writeln!(w)?;
 
     Ok(())
 }
+
+// Extensions section: SAN, key usages and basic constraints by default;
+// `verbose` adds key identifiers, AIA, CRL distribution points, policies
+// and any remaining extension names.
+fn print_extensions_to<W: WriteColor>(w: &mut W, ext: &Extensions, verbose: bool) -> Result<()> {
+    let mut rows: Vec<(&str, String)> = Vec::new();
+    let mut push_list = |label, values: &[String]| {
+        if !values.is_empty() { rows.push((label, values.join(", "))); }
+    };
+    push_list("Subject Alt Names:", &ext.subject_alt_names);
+    push_list("Key Usage:", &ext.key_usage);
+    push_list("Extended Key Usage:", &ext.extended_key_usage);
+    if let Some(bc) = &ext.basic_constraints {
+        let value = match (bc.ca, bc.path_len) {
+            (true, Some(n)) => format!("CA, path length {}", n),
+            (true, None) => "CA".to_string(),
+            (false, _) => "end entity".to_string(),
+        };
+        rows.push(("Basic Constraints:", value));
+    }
+    if verbose {
+        if let Some(ski) = &ext.subject_key_id { rows.push(("Subject Key ID:", ski.clone())); }
+        if let Some(aki) = &ext.authority_key_id { rows.push(("Authority Key ID:", aki.clone())); }
+        for ad in &ext.authority_info_access {
+            rows.push(("Authority Info Access:", format!("{} - {}", ad.method, ad.location)));
+        }
+        if !ext.crl_distribution_points.is_empty() {
+            rows.push(("CRL Distribution Points:", ext.crl_distribution_points.join(", ")));
+        }
+        if !ext.certificate_policies.is_empty() {
+            rows.push(("Certificate Policies:", ext.certificate_policies.join(", ")));
+        }
+        for other in &ext.other {
+            let crit = if other.critical { " (critical)" } else { "" };
+            rows.push(("Other:", format!("{}{}", other.name, crit)));
+        }
+    }
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    print_bold(w, "  Extensions:")?;
+    writeln!(w)?;
+    for (label, value) in rows {
+        write!(w, "    - ")?;
+        print_bold(w, label)?;
+        write!(w, " ")?;
+        print_bold_blue(w, &value)?;
+        writeln!(w)?;
+    }
+    Ok(())
+}
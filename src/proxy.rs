@@ -0,0 +1,287 @@
+use anyhow::{bail, Context, Result};
+use crate::exit::Failure;
+use crate::net::{self, is_timeout, NetOptions};
+use openssl::base64::encode_block;
+use reqwest::Url;
+use std::cell::OnceCell;
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, TcpStream};
+
+// Tunnelling through an HTTP CONNECT or SOCKS5 proxy. The TLS handshake then
+// runs end to end over the tunnel, so the chain is the server's own.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    Http,
+    Socks5,
+}
+
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    pub kind: ProxyKind,
+    host: String,
+    port: u16,
+    credentials: Option<(String, String)>,
+    /// URL as given, credentials included, for the HTTP client.
+    url: String,
+    /// Comma-separated NO_PROXY list.
+    no_proxy: String,
+}
+
+impl Proxy {
+    /// `http://[user:pass@]host[:port]` (port 8080 by default) or
+    /// `socks5://`/`socks5h://[user:pass@]host[:port]` (port 1080). Names are
+    /// always resolved by a SOCKS5 proxy. Without a scheme, as curl takes
+    /// `proxy.corp:3128`, HTTP is assumed.
+    pub fn parse(url: &str) -> Result<Proxy> {
+        let url = match url.contains("://") {
+            true => url.to_string(),
+            false => format!("http://{}", url),
+        };
+        let parsed = Url::parse(&url).with_context(|| format!("invalid proxy URL {}", url))?;
+        let (kind, default_port) = match parsed.scheme() {
+            "http" => (ProxyKind::Http, 8080),
+            "socks5" | "socks5h" => (ProxyKind::Socks5, 1080),
+            other => bail!("unsupported proxy scheme {} (expected http, socks5 or socks5h)", other),
+        };
+        let Some(host) = parsed.host_str() else {
+            bail!("proxy URL {} has no host", url);
+        };
+        let credentials = match parsed.username() {
+            "" => None,
+            user => Some((user.to_string(), parsed.password().unwrap_or_default().to_string())),
+        };
+        Ok(Proxy {
+            kind,
+            host: host.trim_start_matches('[').trim_end_matches(']').to_string(),
+            port: parsed.port().unwrap_or(default_port),
+            credentials,
+            url,
+            no_proxy: env_var(&["NO_PROXY", "no_proxy"]).map(|(_, v)| v).unwrap_or_default(),
+        })
+    }
+
+    /// Whether NO_PROXY exempts `host`: `*`, the exact name or IP, or any
+    /// parent domain (`example.com` and `.example.com` both cover
+    /// `www.example.com`).
+    pub fn bypasses(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase();
+        self.no_proxy.split(',').map(str::trim).filter(|e| !e.is_empty()).any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+            // An optional port is ignored
+            let entry = match entry.rsplit_once(':') {
+                Some((name, port)) if !name.contains(':') && port.parse::<u16>().is_ok() => name,
+                _ => entry,
+            };
+            let entry = entry.trim_start_matches('.').to_ascii_lowercase();
+            host == entry || (host.parse::<IpAddr>().is_err() && host.ends_with(&format!(".{}", entry)))
+        })
+    }
+
+    /// Proxy for the HTTP client (AIA, OCSP and CRL fetches).
+    pub fn for_http(&self) -> Result<reqwest::Proxy> {
+        // reqwest resolves names itself for socks5://, only socks5h:// leaves
+        // them to the proxy as the tunnel does
+        let url = match self.url.strip_prefix("socks5://") {
+            Some(rest) => format!("socks5h://{}", rest),
+            None => self.url.clone(),
+        };
+        let proxy = reqwest::Proxy::all(&url).with_context(|| format!("invalid proxy URL {}", self.label()))?;
+        Ok(proxy.no_proxy(reqwest::NoProxy::from_string(&self.no_proxy)))
+    }
+
+    /// Open a stream to `host:port` through the proxy.
+    pub fn tunnel(&self, host: &str, port: u16, net: &NetOptions) -> Result<TcpStream> {
+        let mut stream = net::connect(&format_addr(&self.host, self.port), net)
+            .with_context(|| format!("failed to reach proxy {}", self.label()))?;
+        let result = match self.kind {
+            ProxyKind::Http => self.http_connect(&mut stream, host, port),
+            ProxyKind::Socks5 => self.socks5_connect(&mut stream, host, port),
+        };
+        result.map_err(|e| {
+            if e.downcast_ref::<Failure>().is_some() {
+                return e;
+            }
+            let msg = format!("proxy {} could not open a tunnel to {}: {:#}", self.label(), format_addr(host, port), e);
+            match e.downcast_ref::<io::Error>() {
+                Some(io) if is_timeout(io.kind()) => Failure::Timeout(msg).into(),
+                _ => Failure::Connection(msg).into(),
+            }
+        })?;
+        Ok(stream)
+    }
+
+    /// URL without credentials, for messages.
+    pub fn label(&self) -> String {
+        let scheme = match self.kind {
+            ProxyKind::Http => "http",
+            ProxyKind::Socks5 => "socks5",
+        };
+        format!("{}://{}", scheme, format_addr(&self.host, self.port))
+    }
+
+    fn http_connect(&self, s: &mut TcpStream, host: &str, port: u16) -> Result<()> {
+        let target = format_addr(host, port);
+        let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\nUser-Agent: tls-doctor/1.0\r\n");
+        if let Some((user, pass)) = &self.credentials {
+            let token = encode_block(format!("{}:{}", user, pass).as_bytes());
+            request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", token));
+        }
+        request.push_str("\r\n");
+        s.write_all(request.as_bytes())?;
+
+        // Headers only, a byte at a time: the TLS handshake follows them
+        let mut head = Vec::new();
+        let mut byte = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            if s.read(&mut byte)? == 0 {
+                bail!("connection closed by proxy");
+            }
+            head.push(byte[0]);
+        }
+        let head = String::from_utf8_lossy(&head);
+        let status = head.lines().next().unwrap_or_default();
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => bail!("proxy answered {}", status),
+        }
+    }
+
+    fn socks5_connect(&self, s: &mut TcpStream, host: &str, port: u16) -> Result<()> {
+        // Greeting (RFC 1928): no authentication, or username/password (RFC 1929)
+        let methods: &[u8] = if self.credentials.is_some() { &[0x00, 0x02] } else { &[0x00] };
+        s.write_all(&[&[0x05, methods.len() as u8][..], methods].concat())?;
+        let mut choice = [0u8; 2];
+        s.read_exact(&mut choice)?;
+        match (choice, &self.credentials) {
+            ([0x05, 0x00], _) => {}
+            ([0x05, 0x02], Some((user, pass))) => {
+                if user.len() > 255 || pass.len() > 255 {
+                    bail!("SOCKS5 username and password are limited to 255 bytes");
+                }
+                let auth = [&[0x01, user.len() as u8][..], user.as_bytes(), &[pass.len() as u8], pass.as_bytes()].concat();
+                s.write_all(&auth)?;
+                let mut status = [0u8; 2];
+                s.read_exact(&mut status)?;
+                if status[1] != 0 {
+                    bail!("SOCKS5 authentication rejected");
+                }
+            }
+            ([0x05, 0xff], _) => bail!("SOCKS5 proxy accepts none of the offered authentication methods"),
+            _ => bail!("unexpected SOCKS5 greeting reply {:02x?}", choice),
+        }
+
+        // CONNECT by IP when given one, by name otherwise
+        let addr = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => [&[0x01][..], &ip.octets()].concat(),
+            Ok(IpAddr::V6(ip)) => [&[0x04][..], &ip.octets()].concat(),
+            Err(_) if host.len() <= 255 => [&[0x03, host.len() as u8][..], host.as_bytes()].concat(),
+            Err(_) => bail!("host name too long for SOCKS5"),
+        };
+        s.write_all(&[&[0x05, 0x01, 0x00][..], &addr, &port.to_be_bytes()].concat())?;
+
+        let mut reply = [0u8; 4];
+        s.read_exact(&mut reply)?;
+        if reply[1] != 0 {
+            let msg = format!(
+                "proxy {} could not open a tunnel to {}: {}",
+                self.label(),
+                format_addr(host, port),
+                socks5_reply(reply[1])
+            );
+            return Err(match reply[1] {
+                0x05 => Failure::Refused(msg),
+                0x06 => Failure::Timeout(msg),
+                _ => Failure::Connection(msg),
+            }
+            .into());
+        }
+        // Skip the bound address
+        let len = match reply[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => {
+                let mut n = [0u8; 1];
+                s.read_exact(&mut n)?;
+                n[0] as usize
+            }
+            other => bail!("unexpected SOCKS5 address type {:#04x}", other),
+        };
+        s.read_exact(&mut vec![0u8; len + 2])?;
+        Ok(())
+    }
+}
+
+/// The proxy of `--proxy`, else HTTPS_PROXY or ALL_PROXY, read and parsed
+/// on first use: commands that never connect ignore a bad setting.
+#[derive(Debug, Default)]
+pub struct LazyProxy {
+    explicit: Option<String>,
+    proxy: OnceCell<Option<Proxy>>,
+}
+
+impl LazyProxy {
+    pub fn new(explicit: Option<&str>) -> Self {
+        LazyProxy { explicit: explicit.map(str::to_string), proxy: OnceCell::new() }
+    }
+
+    pub fn get(&self) -> Result<Option<&Proxy>> {
+        if let Some(proxy) = self.proxy.get() {
+            return Ok(proxy.as_ref());
+        }
+        let setting = match &self.explicit {
+            Some(url) => Some(("--proxy", url.clone())),
+            None => env_var(&["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]),
+        };
+        let proxy = match setting {
+            Some((source, url)) => Some(
+                Proxy::parse(&url).map_err(|e| Failure::Input(format!("invalid proxy setting in {}: {:#}", source, e)))?,
+            ),
+            None => None,
+        };
+        Ok(self.proxy.get_or_init(|| proxy).as_ref())
+    }
+}
+
+impl From<Proxy> for LazyProxy {
+    fn from(proxy: Proxy) -> Self {
+        LazyProxy { explicit: None, proxy: OnceCell::from(Some(proxy)) }
+    }
+}
+
+/// Split `host:port` (IPv6 in brackets) as used for `diag` targets.
+pub fn split_addr(addr: &str) -> Option<(&str, u16)> {
+    let (host, port) = addr.rsplit_once(':')?;
+    Some((host.trim_start_matches('[').trim_end_matches(']'), port.parse().ok()?))
+}
+
+fn format_addr(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+// First of `names` that is set and not empty, with its name.
+fn env_var<'a>(names: &[&'a str]) -> Option<(&'a str, String)> {
+    names.iter().find_map(|&n| env::var(n).ok().filter(|v| !v.is_empty()).map(|v| (n, v)))
+}
+
+// RFC 1928 section 6 reply codes.
+fn socks5_reply(code: u8) -> &'static str {
+    match code {
+        0x01 => "general SOCKS server failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
@@ -0,0 +1,420 @@
+use serde::Serialize;
+
+// Structured diagnosis model. `print.rs` and `validate.rs` fill it in; the
+// text renderer and the JSON serializer both work from the same data.
+
+/// Full result of a `diag` run.
+#[derive(Debug, Serialize)]
+pub struct DiagReport {
+    /// What was diagnosed: `host:port` or the bundle path.
+    pub source: String,
+    /// Detected format of the input file.
+    pub input_format: Option<InputFormat>,
+    /// Ordered chain, leaf first.
+    pub chain: Vec<CertInfo>,
+    /// Certificates present in the input but not part of the chain.
+    pub unused: Vec<CertInfo>,
+    /// Result of `--hostname`, when requested.
+    pub hostname: Option<HostnameCheck>,
+    /// Leaf revocation status from its OCSP responder, with `--ocsp`.
+    pub ocsp: Option<OcspCheck>,
+    /// OCSP response stapled by a live server.
+    pub staple: Option<StapleCheck>,
+    /// Per-certificate CRL results, with `--crl-check`/`--crl`.
+    pub crl: Option<Vec<CrlCheck>>,
+    /// Certificate Transparency SCTs for the leaf, with `--sct`/`--ct-logs`.
+    pub sct: Option<SctCheck>,
+    /// Parameters the live handshake settled on.
+    pub negotiation: Option<Negotiation>,
+    /// Per-address results, with `--all-addresses`.
+    pub addresses: Option<Vec<AddressCheck>>,
+    /// The server's CertificateRequest and the client certificate presented.
+    pub client_auth: Option<ClientAuth>,
+    /// Private key compared with the leaf, with `--key`.
+    pub key: Option<KeyCheck>,
+    pub verdict: Verdict,
+}
+
+/// Private key checked against the leaf certificate.
+#[derive(Debug, Serialize)]
+pub struct KeyCheck {
+    /// Algorithm and size, e.g. `RSA 2048 bits`.
+    pub key: String,
+    /// Subject of the leaf compared with.
+    pub leaf: String,
+    pub leaf_fingerprint: String,
+    pub matches: bool,
+}
+
+/// Certificate file formats read by `diag --file` and `scaffold`.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InputFormat {
+    Pem,
+    Der,
+    Pkcs7Pem,
+    Pkcs7Der,
+    Pkcs12,
+}
+
+/// Display fields of a single certificate.
+#[derive(Debug, Serialize)]
+pub struct CertInfo {
+    pub index: usize,
+    #[serde(rename = "type")]
+    pub cert_type: Option<String>,
+    pub subject: Vec<NameAttr>,
+    pub issuer: Vec<NameAttr>,
+    pub not_before: String,
+    pub not_after: String,
+    /// Whole days until Not After (negative when expired).
+    pub days_remaining: Option<i64>,
+    pub expiry: ExpiryStatus,
+    pub public_key: PublicKeyInfo,
+    pub fingerprint_sha256: String,
+    pub extensions: Extensions,
+}
+
+/// Validity window classification against the `--warn-days`/`--crit-days` thresholds.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryStatus {
+    Ok,
+    Warning,
+    Critical,
+    Expired,
+    NotYetValid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NameAttr {
+    pub label: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PublicKeyInfo {
+    pub algorithm: String,
+    pub bits: u32,
+}
+
+/// Decoded X.509v3 extensions; empty lists mean the extension is absent.
+#[derive(Debug, Default, Serialize)]
+pub struct Extensions {
+    pub subject_alt_names: Vec<String>,
+    pub key_usage: Vec<String>,
+    pub extended_key_usage: Vec<String>,
+    pub basic_constraints: Option<BasicConstraintsInfo>,
+    pub subject_key_id: Option<String>,
+    pub authority_key_id: Option<String>,
+    pub authority_info_access: Vec<AccessDescription>,
+    pub crl_distribution_points: Vec<String>,
+    pub certificate_policies: Vec<String>,
+    /// Extensions without a dedicated field, by short name or OID.
+    pub other: Vec<OtherExtension>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BasicConstraintsInfo {
+    pub ca: bool,
+    pub path_len: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccessDescription {
+    pub method: String,
+    pub location: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OtherExtension {
+    pub name: String,
+    pub critical: bool,
+}
+
+/// Whether the leaf's subjectAltName covers the requested name.
+#[derive(Debug, Serialize)]
+pub struct HostnameCheck {
+    pub name: String,
+    pub matched: bool,
+    /// SAN entry that matched, e.g. `DNS:*.example.com`.
+    pub matched_entry: Option<String>,
+}
+
+/// OCSP status of the leaf certificate.
+#[derive(Debug, Serialize)]
+pub struct OcspCheck {
+    /// Responder URL the answer came from.
+    pub responder: Option<String>,
+    pub status: RevocationStatus,
+    /// CRLReason name when revoked.
+    pub reason: Option<String>,
+    pub revocation_time: Option<String>,
+    pub this_update: Option<String>,
+    pub next_update: Option<String>,
+    /// Whether the response is within its thisUpdate/nextUpdate window.
+    pub current: bool,
+    /// Why no verified answer could be obtained.
+    pub error: Option<String>,
+}
+
+impl OcspCheck {
+    pub fn failed(responder: Option<String>, error: impl Into<String>) -> Self {
+        OcspCheck {
+            responder,
+            status: RevocationStatus::Error,
+            reason: None,
+            revocation_time: None,
+            this_update: None,
+            next_update: None,
+            current: false,
+            error: Some(error.into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RevocationStatus {
+    Good,
+    Revoked,
+    Unknown,
+    /// No verified answer (unreachable responder, bad signature...).
+    Error,
+}
+
+/// CRL status of one certificate in the chain.
+#[derive(Debug, Serialize)]
+pub struct CrlCheck {
+    /// Position of the certificate in the chain (1 = leaf).
+    pub index: usize,
+    pub subject: String,
+    /// `--crl` file or distribution point URL the CRL came from.
+    pub source: Option<String>,
+    pub status: RevocationStatus,
+    pub reason: Option<String>,
+    pub revocation_date: Option<String>,
+    pub this_update: Option<String>,
+    pub next_update: Option<String>,
+    /// nextUpdate is in the past.
+    pub stale: bool,
+    /// No distribution point could be fetched.
+    pub unreachable: bool,
+    pub error: Option<String>,
+}
+
+/// SCTs found for the leaf and whether they satisfy the CT policy.
+#[derive(Debug, Default, Serialize)]
+pub struct SctCheck {
+    pub scts: Vec<SctInfo>,
+    /// Distinct-log embedded SCTs needed for the leaf's lifetime (2 up to 180 days, else 3).
+    pub required_embedded: usize,
+    /// Enough SCTs from distinct logs, embedded or delivered in the handshake (2).
+    pub compliant: bool,
+    /// SCT lists or entries that could not be decoded.
+    pub errors: Vec<String>,
+}
+
+/// One Signed Certificate Timestamp (RFC 6962 section 3.2).
+#[derive(Debug, Serialize)]
+pub struct SctInfo {
+    pub source: SctSource,
+    /// Base64 SHA-256 of the log key, as in log lists.
+    pub log_id: String,
+    /// Log description from `--ct-logs`.
+    pub log: Option<String>,
+    pub timestamp: String,
+    pub signature_algorithm: String,
+    /// Signature check result; None when the log is not in `--ct-logs`.
+    pub verified: Option<bool>,
+    /// Why a known log's signature could not be checked.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SctSource {
+    /// Precertificate SCT list extension in the leaf.
+    Embedded,
+    /// signed_certificate_timestamp TLS extension.
+    TlsExtension,
+    /// Extension of the stapled OCSP response.
+    Ocsp,
+}
+
+/// Chain served by one resolved address of the server.
+#[derive(Debug, Serialize)]
+pub struct AddressCheck {
+    /// `ip:port` connected to.
+    pub address: String,
+    /// SHA-256 fingerprints of the served chain, leaf first.
+    pub chain_fingerprints: Vec<String>,
+    /// Why the served chain does not verify (e.g. a missing intermediate).
+    pub verify_error: Option<String>,
+    /// The leaf differs from the one most addresses serve.
+    pub different_leaf: bool,
+    /// Connection or handshake failure.
+    pub error: Option<String>,
+}
+
+/// Client certificate request of a live server (mutual TLS).
+#[derive(Debug, Serialize)]
+pub struct ClientAuth {
+    /// The server sent a CertificateRequest.
+    pub requested: bool,
+    /// Distinguished names of the CAs the server accepts; empty means any.
+    pub acceptable_cas: Vec<String>,
+    /// Signature schemes accepted for the client's CertificateVerify.
+    pub signature_algorithms: Vec<String>,
+    /// Subject of the certificate presented with `--client-cert`.
+    pub presented: Option<String>,
+    /// Whether the presented chain is issued by one of `acceptable_cas`.
+    pub presented_accepted: Option<bool>,
+}
+
+/// Protocol, cipher suite and extensions negotiated with a live server.
+#[derive(Debug, Serialize)]
+pub struct Negotiation {
+    /// Server name sent in the ClientHello; None with `--no-sni` or an IP.
+    pub sni: Option<String>,
+    /// e.g. `TLSv1.3`.
+    pub protocol: String,
+    /// IANA cipher suite name.
+    pub cipher: Option<String>,
+    /// Ephemeral key exchange group (curve or DH size); None for RSA key exchange.
+    pub key_exchange: Option<String>,
+    /// Protocol selected from our `h2`/`http/1.1` offer.
+    pub alpn: Option<String>,
+    pub session_reused: bool,
+    /// Scheme of the server's handshake signature.
+    pub signature_algorithm: Option<String>,
+}
+
+/// What the server stapled in reply to status_request.
+#[derive(Debug, Serialize)]
+pub struct StapleCheck {
+    pub state: StapleState,
+    /// Decoded response when it covered the leaf.
+    pub response: Option<OcspCheck>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StapleState {
+    NoStaple,
+    Good,
+    Revoked,
+    Unknown,
+    /// Outside its thisUpdate/nextUpdate window.
+    Expired,
+    /// Validly signed but about another certificate.
+    WrongCertificate,
+    /// Malformed, unsuccessful or badly signed.
+    Invalid,
+}
+
+/// Result of a `scan` run.
+#[derive(Debug, Serialize)]
+pub struct ScanReport {
+    /// `host:port` that was scanned.
+    pub source: String,
+    /// One entry per probed version, oldest first.
+    pub protocols: Vec<ProtocolSupport>,
+    pub grade: Grade,
+    pub status: Status,
+    /// Why the grade is not A.
+    pub issues: Vec<Issue>,
+}
+
+/// Suites a server accepts for one protocol version.
+#[derive(Debug, Serialize)]
+pub struct ProtocolSupport {
+    /// e.g. `TLSv1.2`.
+    pub protocol: String,
+    pub supported: bool,
+    /// IANA names of the accepted suites, most preferred first.
+    pub ciphers: Vec<String>,
+    /// The server picks by its own order; None with fewer than two suites.
+    pub server_preference: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum Grade {
+    A,
+    B,
+    C,
+    F,
+}
+
+/// Outcome of chain validation plus any bundle consistency issues.
+#[derive(Debug, Serialize)]
+pub struct Verdict {
+    /// True when no error-level issue was found (warnings are allowed).
+    pub valid: bool,
+    pub status: Status,
+    pub issues: Vec<Issue>,
+    /// Root the chain was verified against, when validation succeeded.
+    pub trust_anchor: Option<TrustAnchor>,
+}
+
+impl Verdict {
+    /// Derive the overall status from the most severe issue.
+    pub fn from_issues(issues: Vec<Issue>) -> Self {
+        let status = if issues.iter().any(|i| i.severity == Severity::Error) {
+            Status::Invalid
+        } else if !issues.is_empty() {
+            Status::Warnings
+        } else {
+            Status::Valid
+        };
+        Verdict { valid: status != Status::Invalid, status, issues, trust_anchor: None }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrustAnchor {
+    pub subject: String,
+    pub fingerprint_sha256: String,
+    pub source: AnchorSource,
+}
+
+/// Which configured trust source supplied the anchor.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnchorSource {
+    System,
+    CaFile,
+    CaDir,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Valid,
+    Warnings,
+    Invalid,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Issue {
+    pub fn warning(message: impl Into<String>) -> Self {
+        Issue { severity: Severity::Warning, message: message.into() }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Issue { severity: Severity::Error, message: message.into() }
+    }
+}
@@ -1,63 +1,28 @@
 use anyhow::{bail, Context, Result};
-use openssl::x509::X509;
+use crate::extensions::aia_ca_issuers_urls;
+use crate::chain::order_chain_leaf_to_root;
+use crate::http;
+use crate::input::{load_certificates, parse_certificates};
+use crate::issuers::IssuerCache;
+use crate::proxy::LazyProxy;
+use crate::util::{cn_label, fingerprint_sha256, format_name_human};
+use crate::validate::{verified_chain, LazyTrustStore, TrustStore};
+use openssl::asn1::Asn1Time;
+use openssl::x509::{X509Ref, X509};
 use reqwest::blocking::Client;
 use std::collections::HashSet;
-use std::fs;
+use std::fmt;
 use std::path::Path;
-use x509_parser::prelude::*;
 
 /// Build a best-effort certificate bundle (leaf -> root) starting from a leaf file.
-/// Follows AIA caIssuers URIs to fetch intermediates (and possibly root) online.
-pub fn build_bundle_from_leaf_file(input_path: &Path) -> Result<Vec<X509>> {
-    let data = fs::read(input_path)
-        .with_context(|| format!("failed to read input file {}", input_path.display()))?;
-    let leaf = parse_single_cert_pem_or_der(&data)?;
-    build_bundle_from_leaf(leaf)
-}
-
-pub fn write_pem_bundle(output_path: &Path, chain: &[X509]) -> Result<()> {
-    let mut out = Vec::new();
-    for cert in chain {
-        let pem = cert.to_pem()?;
-        out.extend_from_slice(&pem);
-    }
-    fs::write(output_path, &out)
-        .with_context(|| format!("failed to write bundle to {}", output_path.display()))?;
-    Ok(())
-}
-
-fn parse_single_cert_pem_or_der(data: &[u8]) -> Result<X509> {
-    // Try PEM first (may contain multiple; take the first)
-    if let Ok(stack) = X509::stack_from_pem(data) {
-        if let Some(c) = stack.into_iter().next() {
-            return Ok(c);
-        }
-    }
-    // Then try DER
-    let cert = X509::from_der(data).context("input is neither PEM nor DER certificate")?;
-    Ok(cert)
-}
-
-fn aia_ca_issuers_urls(cert: &X509) -> Vec<String> {
-    // Use x509-parser for robust AIA parsing
-    let der = match cert.to_der() { Ok(d) => d, Err(_) => return vec![] };
-    if let Ok((_, parsed)) = X509Certificate::from_der(&der) {
-        for ext in parsed.extensions() {
-        if let ParsedExtension::AuthorityInfoAccess(aia) = ext.parsed_extension() {
-                let mut urls = Vec::new();
-                for ad in &aia.accessdescs {
-                    // 1.3.6.1.5.5.7.48.2 = id-ad-caIssuers
-                    if ad.access_method.to_id_string() == "1.3.6.1.5.5.7.48.2" {
-                        if let GeneralName::URI(uri) = &ad.access_location {
-                            urls.push(uri.to_string());
-                        }
-                    }
-                }
-                if !urls.is_empty() { return urls; }
-            }
-        }
-    }
-    vec![]
+/// Issuers come from local directories and files, the cache, AIA caIssuers
+/// URIs fetched online, or the trust store.
+pub fn build_bundle_from_leaf_file(input_path: &Path, opts: &ScaffoldOptions) -> Result<Bundle> {
+    let (_, certs) = load_certificates(input_path, None)?;
+    // A bundle is accepted too; start from its leaf
+    let (seq, _) = order_chain_leaf_to_root(&certs);
+    let leaf = seq.first().map_or_else(|| certs[0].clone(), |c| (*c).to_owned());
+    build_bundle_from_leaf(leaf, opts)
 }
 
 fn fetch_issuer_from_url(client: &Client, url: &str) -> Result<Vec<X509>> {
@@ -67,76 +32,352 @@ fn fetch_issuer_from_url(client: &Client, url: &str) -> Result<Vec<X509>> {
     }
     let bytes = resp.bytes()?.to_vec();
 
-    // Try X.509 DER
-    if let Ok(cert) = X509::from_der(&bytes) {
-        return Ok(vec![cert]);
-    }
+    // DER, PEM or a PKCS#7 bundle (.p7c)
+    let (_, certs) = parse_certificates(&bytes, None).with_context(|| format!("unrecognized certificate format from {}", url))?;
+    Ok(certs)
+}
+
+fn is_self_issued(cert: &X509) -> bool {
+    cert.subject_name().to_der().ok() == cert.issuer_name().to_der().ok()
+}
 
-    // Try PEM (could be one or many)
-    if let Ok(stack) = X509::stack_from_pem(&bytes) {
-        return Ok(stack);
+/// Where `scaffold` may look for issuers besides AIA.
+#[derive(Default)]
+pub struct ScaffoldOptions {
+    /// AIA fetches go through this proxy when given, like `diag`'s connection.
+    pub proxy: LazyProxy,
+    /// Issuers fetched earlier, and where new fetches are kept.
+    pub cache: Option<IssuerCache>,
+    /// Certificates of `--intermediates-dir`, consulted first.
+    pub local: Vec<X509>,
+    /// Never fetch; only local sources and the cache are used.
+    pub offline: bool,
+    /// Certificates of `--intermediates-file` (e.g. the CCADB intermediate
+    /// list), tried when AIA yields nothing.
+    pub bundled: Vec<X509>,
+    /// Trust store tried last, for issuers AIA does not lead to.
+    pub trust: Option<LazyTrustStore>,
+}
+
+/// Where a certificate of the bundle came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssuerSource {
+    /// The `--input` file.
+    Input,
+    /// `--intermediates-dir`.
+    LocalDir,
+    /// The issuer cache, by subject.
+    Cache,
+    /// An AIA caIssuers URL, fetched now or by an earlier run (`cached`).
+    Aia { url: String, cached: bool },
+    /// `--intermediates-file`.
+    BundleFile,
+    /// The system trust store.
+    TrustStore,
+}
+
+impl fmt::Display for IssuerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssuerSource::Input => f.write_str("input"),
+            IssuerSource::LocalDir => f.write_str("--intermediates-dir"),
+            IssuerSource::Cache => f.write_str("issuer cache"),
+            IssuerSource::Aia { url, cached: false } => write!(f, "AIA {}", url),
+            IssuerSource::Aia { url, cached: true } => write!(f, "AIA {} (cached)", url),
+            IssuerSource::BundleFile => f.write_str("--intermediates-file"),
+            IssuerSource::TrustStore => f.write_str("system trust store"),
+        }
     }
+}
 
-    bail!("unrecognized certificate format from {}", url)
+/// What `scaffold` built: the chain, leaf first, where each certificate came
+/// from, and notes worth a look (several plausible issuers, same-named
+/// certificates that did not sign, the steps that found no issuer).
+#[derive(Debug)]
+pub struct Bundle {
+    pub chain: Vec<X509>,
+    /// One per certificate of `chain`.
+    pub sources: Vec<IssuerSource>,
+    pub notes: Vec<String>,
 }
 
-fn is_self_issued(cert: &X509) -> bool {
-    cert.subject_name().to_der().ok() == cert.issuer_name().to_der().ok()
+impl Bundle {
+    fn push(&mut self, cert: X509, source: IssuerSource) {
+        self.chain.push(cert);
+        self.sources.push(source);
+    }
 }
 
-pub fn build_bundle_from_leaf(leaf: X509) -> Result<Vec<X509>> {
-    let client = Client::builder()
-        .user_agent("tls-doctor/1.0")
-        .redirect(reqwest::redirect::Policy::limited(5))
-        .timeout(std::time::Duration::from_secs(15))
-        .build()?;
+pub fn build_bundle_from_leaf(leaf: X509, opts: &ScaffoldOptions) -> Result<Bundle> {
+    let mut bundle = Bundle { chain: Vec::new(), sources: Vec::new(), notes: Vec::new() };
+    bundle.push(leaf, IssuerSource::Input);
+    complete_chain(&mut bundle, &[], opts)?;
+    Ok(bundle)
+}
+
+/// Fix a bundle: its leaf is found with `order_chain_leaf_to_root`, then the
+/// chain is rebuilt from the bundle's own certificates first and the usual
+/// sources for what is missing. Duplicates and certificates that do not
+/// issue anything in the chain are left out.
+pub fn repair_bundle(certs: &[X509], opts: &ScaffoldOptions) -> Result<Bundle> {
+    let mut unique = HashSet::new();
+    let distinct: Vec<X509> = certs.iter().filter(|c| unique.insert(c.to_der().unwrap_or_default())).cloned().collect();
+    let (seq, _) = order_chain_leaf_to_root(&distinct);
+    let leaf = seq.first().map_or_else(|| distinct[0].clone(), |c| (*c).to_owned());
 
-    let mut chain: Vec<X509> = vec![leaf];
+    let mut bundle = Bundle { chain: Vec::new(), sources: Vec::new(), notes: Vec::new() };
+    bundle.push(leaf, IssuerSource::Input);
+    complete_chain(&mut bundle, &distinct, opts)?;
+    Ok(bundle)
+}
+
+// Append issuers to `bundle` until a self-issued certificate or a gap.
+// `own` (the bundle being repaired) is searched before any other source.
+fn complete_chain(bundle: &mut Bundle, own: &[X509], opts: &ScaffoldOptions) -> Result<()> {
+    let mut client: Option<Client> = None;
     let mut seen: HashSet<Vec<u8>> = HashSet::new();
     // track subjects we have to avoid loops
-    seen.insert(chain[0].subject_name().to_der().unwrap_or_default());
+    seen.extend(bundle.chain.iter().map(|c| c.subject_name().to_der().unwrap_or_default()));
 
     loop {
-        let current = chain.last().unwrap();
+        let current = bundle.chain.last().unwrap();
         // Stop at self-issued (likely root) to avoid infinite fetch
         if is_self_issued(current) { break; }
 
-        let urls = aia_ca_issuers_urls(current);
-        if urls.is_empty() {
-            // No AIA; stop
-            break;
-        }
-
-        let mut next_issuer: Option<X509> = None;
-        for url in urls {
-            if let Ok(candidates) = fetch_issuer_from_url(&client, &url) {
-                // pick the first whose subject matches current.issuer
-                let cur_iss = current.issuer_name().to_der().unwrap_or_default();
-                for cand in candidates {
-                    let subj = cand.subject_name().to_der().unwrap_or_default();
-                    if subj == cur_iss {
-                        next_issuer = Some(cand);
-                        break;
-                    }
-                }
-            }
-            if next_issuer.is_some() { break; }
-        }
-
-        match next_issuer {
-            Some(issuer) => {
+        let found = match select_issuer(current, own.to_vec(), &IssuerSource::Input, &mut bundle.notes) {
+            Some(issuer) => Some((issuer, IssuerSource::Input)),
+            None => find_issuer(current, opts, &mut client, &mut bundle.notes)?,
+        };
+        match found {
+            Some((issuer, source)) => {
                 let subj = issuer.subject_name().to_der().unwrap_or_default();
                 if seen.contains(&subj) {
                     // loop or duplicate
                     break;
                 }
-                chain.push(issuer);
+                bundle.push(issuer, source);
                 seen.insert(subj);
                 continue;
             }
             None => break,
         }
     }
+    Ok(())
+}
+
+/// One line of `scaffold --repair`'s report, comparing the original bundle
+/// with the repaired one. Positions are 0-based, leaf first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Kept { label: String, at: usize },
+    Moved { label: String, from: usize, to: usize },
+    Added { label: String, to: usize, source: IssuerSource },
+    Duplicate { label: String, from: usize, of: usize },
+    /// Not part of the repaired chain; `root` when it is the self-signed
+    /// issuer of its top (left out by `--exclude-root`).
+    Removed { label: String, from: usize, root: bool },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Kept { label, at } => write!(f, "  [{}] {}", at, label),
+            Change::Moved { label, from, to } => write!(f, "~ [{}] {} (moved from [{}])", to, label, from),
+            Change::Added { label, to, source } => write!(f, "+ [{}] {} ({})", to, label, source),
+            Change::Duplicate { label, from, of } => write!(f, "- [{}] {} (duplicate of [{}])", from, label, of),
+            Change::Removed { label, from, root: true } => write!(f, "- [{}] {} (root excluded)", from, label),
+            Change::Removed { label, from, root: false } => write!(f, "- [{}] {} (not part of the chain)", from, label),
+        }
+    }
+}
+
+/// Diff-style comparison of `original` with `repaired`: the repaired chain in
+/// order, then what was dropped.
+pub fn diff_bundles(original: &[X509], repaired: &Bundle) -> Vec<Change> {
+    let der: Vec<Vec<u8>> = original.iter().map(|c| c.to_der().unwrap_or_default()).collect();
+    let mut placed = vec![false; original.len()];
+    let mut changes = Vec::new();
+    for (to, (cert, source)) in repaired.chain.iter().zip(&repaired.sources).enumerate() {
+        let label = cn_label(cert);
+        let cert_der = cert.to_der().unwrap_or_default();
+        match (0..original.len()).find(|&i| !placed[i] && der[i] == cert_der) {
+            Some(from) => {
+                placed[from] = true;
+                changes.push(match from == to {
+                    true => Change::Kept { label, at: to },
+                    false => Change::Moved { label, from, to },
+                });
+            }
+            None => changes.push(Change::Added { label, to, source: source.clone() }),
+        }
+    }
+    for from in (0..original.len()).filter(|&i| !placed[i]) {
+        let label = cn_label(&original[from]);
+        changes.push(match (0..from).find(|&i| der[i] == der[from]) {
+            Some(of) => Change::Duplicate { label, from, of },
+            None => {
+                let cert = &original[from];
+                let root = is_self_issued(cert) && repaired.chain.last().is_some_and(|top| is_issuer_of(cert, top));
+                Change::Removed { label, from, root }
+            }
+        });
+    }
+    changes
+}
+
+// The issuer of `cert`: from `--intermediates-dir`, the cache, AIA, then,
+// when AIA yields nothing, `--intermediates-file` and the trust store. The
+// steps that were tried are noted when all fail.
+fn find_issuer(
+    cert: &X509,
+    opts: &ScaffoldOptions,
+    client: &mut Option<Client>,
+    notes: &mut Vec<String>,
+) -> Result<Option<(X509, IssuerSource)>> {
+    let cached = opts.cache.as_ref().map(|cache| cache.by_subject(cert.issuer_name())).unwrap_or_default();
+    for (candidates, source) in [(opts.local.clone(), IssuerSource::LocalDir), (cached, IssuerSource::Cache)] {
+        if let Some(issuer) = select_issuer(cert, candidates, &source, notes) {
+            return Ok(Some((issuer, source)));
+        }
+    }
+
+    let mut failed = Vec::new();
+    let urls = aia_ca_issuers_urls(cert);
+    if urls.is_empty() {
+        failed.push("no AIA caIssuers URL".to_string());
+    }
+    for url in urls {
+        let (candidates, cached) = match opts.cache.as_ref().and_then(|cache| cache.by_url(&url)) {
+            Some(cached) => (cached, true),
+            None if opts.offline => {
+                failed.push(format!("AIA {} not fetched (--offline)", url));
+                continue;
+            }
+            None => {
+                let client = match client {
+                    Some(client) => client,
+                    None => client.insert(http::client(opts.proxy.get()?)?),
+                };
+                let fetched = match fetch_issuer_from_url(client, &url) {
+                    Ok(fetched) => fetched,
+                    Err(e) => {
+                        failed.push(format!("AIA {:#}", e));
+                        continue;
+                    }
+                };
+                if let Some(cache) = &opts.cache {
+                    cache.store(&url, &fetched);
+                }
+                (fetched, false)
+            }
+        };
+        let source = IssuerSource::Aia { url, cached };
+        if let Some(issuer) = select_issuer(cert, candidates, &source, notes) {
+            return Ok(Some((issuer, source)));
+        }
+        failed.push(format!("{} served no issuer of it", source));
+    }
+
+    // No usable AIA: the intermediates file, then the trust store
+    if !opts.bundled.is_empty() {
+        if let Some(issuer) = select_issuer(cert, opts.bundled.clone(), &IssuerSource::BundleFile, notes) {
+            return Ok(Some((issuer, IssuerSource::BundleFile)));
+        }
+        failed.push("not in --intermediates-file".to_string());
+    }
+    if let Some(trust) = &opts.trust {
+        let candidates = trust.get()?.issuer_candidates(cert);
+        if let Some(issuer) = select_issuer(cert, candidates, &IssuerSource::TrustStore, notes) {
+            return Ok(Some((issuer, IssuerSource::TrustStore)));
+        }
+        failed.push("not in the system trust store".to_string());
+    }
+
+    notes.push(format!(
+        "no issuer found for {} (issued by {}): {}",
+        cn_label(cert),
+        format_name_human(cert.issuer_name().entries()),
+        failed.join("; ")
+    ));
+    Ok(None)
+}
+
+/// Whether `cand` issued `cert`: its subject is `cert`'s issuer name, its
+/// Subject Key Identifier equals `cert`'s Authority Key Identifier (when both
+/// are present), and its key verifies `cert`'s signature. The name alone is
+/// not enough: re-keyed and cross-signed CAs share it.
+fn is_issuer_of(cand: &X509Ref, cert: &X509Ref) -> bool {
+    if cand.subject_name().to_der().ok() != cert.issuer_name().to_der().ok() {
+        return false;
+    }
+    if let (Some(aki), Some(ski)) = (cert.authority_key_id(), cand.subject_key_id()) {
+        if aki.as_slice() != ski.as_slice() {
+            return false;
+        }
+    }
+    cand.public_key().is_ok_and(|key| cert.verify(&key).unwrap_or(false))
+}
+
+// The issuer of `cert` among `candidates` from `source`, preferring one that
+// is currently valid. Ambiguity and same-named impostors are noted.
+fn select_issuer(cert: &X509, candidates: Vec<X509>, source: &IssuerSource, notes: &mut Vec<String>) -> Option<X509> {
+    let issuer_name = cert.issuer_name().to_der().unwrap_or_default();
+    let (mut plausible, namesakes): (Vec<X509>, Vec<X509>) = candidates
+        .into_iter()
+        .filter(|cand| cand.subject_name().to_der().ok().as_ref() == Some(&issuer_name))
+        .partition(|cand| is_issuer_of(cand, cert));
+    let mut unique = HashSet::new();
+    plausible.retain(|cand| unique.insert(cand.to_der().unwrap_or_default()));
+    // Stable: the source's order decides among equally valid candidates
+    plausible.sort_by_key(|cand| !is_current(cand));
+
+    match plausible.len() {
+        0 if !namesakes.is_empty() => notes.push(format!(
+            "{} certificate(s) named {} from {} did not sign {} (key identifier or signature mismatch)",
+            namesakes.len(),
+            cn_label(&namesakes[0]),
+            source,
+            cn_label(cert)
+        )),
+        0 | 1 => {}
+        n => notes.push(format!(
+            "{} plausible issuers of {} from {}; using the one valid until {} (SHA-256 {})",
+            n,
+            cn_label(cert),
+            source,
+            plausible[0].not_after(),
+            fingerprint_sha256(&plausible[0]).unwrap_or_default()
+        )),
+    }
+    plausible.into_iter().next()
+}
+
+/// End the chain with the trust anchor it verifies to in `trust` (the system
+/// store), unless it already ends there. A chain that does not verify is
+/// left as is, with a note.
+pub fn add_trust_anchor(bundle: &mut Bundle, trust: &TrustStore) -> Result<()> {
+    let intermediates: Vec<&X509Ref> = bundle.chain[1..].iter().map(|c| c.as_ref()).collect();
+    match verified_chain(&bundle.chain[0], &intermediates, trust)? {
+        Ok(path) => {
+            let anchor = path.last().expect("verified chain includes the anchor");
+            let last = bundle.chain.last().expect("chain has the leaf");
+            if anchor.to_der()? != last.to_der()? && !is_self_issued(last) {
+                bundle.push(anchor.clone(), IssuerSource::TrustStore);
+            }
+        }
+        Err(msg) => bundle.notes.push(format!("no trust anchor added: chain does not verify against the system trust store: {}", msg)),
+    }
+    Ok(())
+}
+
+/// Drop the self-issued root ending the chain; a self-signed leaf stays.
+pub fn exclude_root(bundle: &mut Bundle) {
+    if bundle.chain.len() > 1 && bundle.chain.last().is_some_and(is_self_issued) {
+        bundle.chain.pop();
+        bundle.sources.pop();
+    }
+}
 
-    Ok(chain)
+fn is_current(cert: &X509) -> bool {
+    Asn1Time::days_from_now(0).is_ok_and(|now| cert.not_before() <= now && cert.not_after() >= now)
 }
@@ -0,0 +1,193 @@
+use anyhow::Result;
+use crate::ffi;
+use crate::negotiation::{is_cbc_suite, is_legacy_protocol, is_rsa_key_exchange, legacy_connector_builder};
+use crate::net::{self, NetOptions};
+use crate::report::{Grade, Issue, ProtocolSupport, ScanReport, Verdict};
+use openssl::ssl::{Ssl, SslConnector, SslVerifyMode, SslVersion};
+
+// Protocol and cipher suite enumeration: one handshake per version and per
+// suite, offering nothing else, then repeated handshakes to learn the order
+// the server picks in.
+
+/// Versions probed, oldest first, with their OpenSSL names.
+const VERSIONS: [(SslVersion, &str); 4] = [
+    (SslVersion::TLS1, "TLSv1"),
+    (SslVersion::TLS1_1, "TLSv1.1"),
+    (SslVersion::TLS1_2, "TLSv1.2"),
+    (SslVersion::TLS1_3, "TLSv1.3"),
+];
+
+/// Every TLS 1.3 suite OpenSSL implements; the default list leaves out CCM.
+const TLS13_SUITES: &str =
+    "TLS_AES_256_GCM_SHA384:TLS_CHACHA20_POLY1305_SHA256:TLS_AES_128_GCM_SHA256:TLS_AES_128_CCM_SHA256:TLS_AES_128_CCM_8_SHA256";
+
+/// A suite the local OpenSSL can offer.
+struct Suite {
+    /// OpenSSL name, as used in cipher lists.
+    name: String,
+    /// IANA name, as reported.
+    standard_name: String,
+    tls13: bool,
+    /// Oldest protocol the suite can be used with (index into VERSIONS).
+    min_version: usize,
+}
+
+/// Server under scan; every probe starts from the same client context.
+struct Target<'a> {
+    connector: SslConnector,
+    addr: String,
+    host: &'a str,
+    net: &'a NetOptions,
+}
+
+/// Probe `host:port` with each protocol version and suite and grade the
+/// result. Every probe is bounded by the timeouts of `net`.
+pub fn scan_server(host: &str, port: u16, net: &NetOptions) -> Result<ScanReport> {
+    let addr = format!("{}:{}", host, port);
+    let suites = local_suites()?;
+    let mut builder = legacy_connector_builder()?;
+    builder.set_verify(SslVerifyMode::NONE);
+    let connector = builder.build();
+    let target = Target { connector, addr: addr.clone(), host, net };
+    let mut protocols = Vec::new();
+    for (i, (version, label)) in VERSIONS.iter().enumerate() {
+        let candidates: Vec<&Suite> =
+            suites.iter().filter(|s| s.tls13 == (*version == SslVersion::TLS1_3) && s.min_version <= i).collect();
+        let mut accepted = Vec::new();
+        for suite in &candidates {
+            if probe(&target, *version, &[suite])?.is_some() {
+                accepted.push(*suite);
+            }
+        }
+        let (ciphers, server_preference) = preference_order(&target, *version, accepted)?;
+        protocols.push(ProtocolSupport {
+            protocol: label.to_string(),
+            supported: !ciphers.is_empty(),
+            ciphers,
+            server_preference,
+        });
+    }
+
+    let issues = scan_issues(&protocols);
+    let grade = grade(&protocols);
+    let verdict = Verdict::from_issues(issues);
+    Ok(ScanReport { source: target.addr, protocols, grade, status: verdict.status, issues: verdict.issues })
+}
+
+/// Errors for legacy protocols and broken suites, warnings for CBC, RSA key
+/// exchange and missing TLS 1.3.
+pub fn scan_issues(protocols: &[ProtocolSupport]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if !protocols.iter().any(|p| p.supported) {
+        issues.push(Issue::error("server accepts none of the probed protocol versions"));
+        return issues;
+    }
+    for p in protocols.iter().filter(|p| p.supported && is_legacy_protocol(&p.protocol)) {
+        issues.push(Issue::error(format!("server accepts deprecated protocol {}", p.protocol)));
+    }
+    if !protocols.iter().any(|p| p.supported && p.protocol == "TLSv1.3") {
+        issues.push(Issue::warning("server does not support TLSv1.3"));
+    }
+    let mut seen: Vec<&str> = Vec::new();
+    for cipher in protocols.iter().flat_map(|p| &p.ciphers) {
+        if seen.contains(&cipher.as_str()) {
+            continue;
+        }
+        seen.push(cipher);
+        if is_insecure_suite(cipher) {
+            issues.push(Issue::error(format!("server accepts insecure cipher suite {}", cipher)));
+        } else if is_rsa_key_exchange(cipher) {
+            issues.push(Issue::warning(format!("server accepts RSA key exchange (no forward secrecy): {}", cipher)));
+        } else if is_cbc_suite(cipher) {
+            issues.push(Issue::warning(format!("server accepts CBC cipher suite {}", cipher)));
+        }
+    }
+    issues
+}
+
+/// A: modern only; B: CBC suites, RSA key exchange or no TLS 1.3; C: legacy
+/// protocols; F: insecure suites or nothing accepted.
+pub fn grade(protocols: &[ProtocolSupport]) -> Grade {
+    let supported: Vec<&ProtocolSupport> = protocols.iter().filter(|p| p.supported).collect();
+    let mut ciphers = supported.iter().flat_map(|p| &p.ciphers);
+    if supported.is_empty() || ciphers.clone().any(|c| is_insecure_suite(c)) {
+        Grade::F
+    } else if supported.iter().any(|p| is_legacy_protocol(&p.protocol)) {
+        Grade::C
+    } else if !supported.iter().any(|p| p.protocol == "TLSv1.3")
+        || ciphers.any(|c| is_cbc_suite(c) || is_rsa_key_exchange(c))
+    {
+        Grade::B
+    } else {
+        Grade::A
+    }
+}
+
+// NULL, anonymous, export-grade, RC4, DES/3DES and MD5 suites.
+fn is_insecure_suite(cipher: &str) -> bool {
+    ["_NULL_", "_anon_", "_EXPORT", "_RC4_", "_DES", "_3DES_", "_MD5"].iter().any(|m| cipher.contains(m))
+}
+
+// Offer the accepted suites repeatedly, removing the one the server picked,
+// to recover its order. Offering them reversed tells whether that order is
+// the server's or just ours.
+fn preference_order(target: &Target, version: SslVersion, mut left: Vec<&Suite>) -> Result<(Vec<String>, Option<bool>)> {
+    let reversed: Vec<&Suite> = left.iter().rev().copied().collect();
+    let mut order = Vec::new();
+    while !left.is_empty() {
+        let Some(chosen) = probe(target, version, &left)? else {
+            // Server stopped agreeing; keep the rest in our order
+            order.extend(left.iter().map(|s| s.standard_name.clone()));
+            break;
+        };
+        left.retain(|s| s.standard_name != chosen);
+        order.push(chosen);
+    }
+    let server_preference = match order.len() {
+        0 | 1 => None,
+        _ => Some(probe(target, version, &reversed)?.as_deref() == order.first().map(String::as_str)),
+    };
+    Ok((order, server_preference))
+}
+
+// One handshake offering only `version` and `suites`; the chosen suite, or
+// None when the server refused.
+fn probe(target: &Target, version: SslVersion, suites: &[&Suite]) -> Result<Option<String>> {
+    let names = suites.iter().map(|s| s.name.as_str()).collect::<Vec<_>>().join(":");
+    let mut config = target.connector.configure()?.verify_hostname(false);
+    config.set_min_proto_version(Some(version))?;
+    config.set_max_proto_version(Some(version))?;
+    if version == SslVersion::TLS1_3 {
+        config.set_ciphersuites(&names)?;
+    } else {
+        config.set_cipher_list(&format!("{}@SECLEVEL=0", names))?;
+    }
+
+    let tcp = net::connect(&target.addr, target.net)?;
+    let Ok(stream) = config.connect(target.host, tcp) else {
+        return Ok(None);
+    };
+    Ok(stream.ssl().current_cipher().map(|c| c.standard_name().unwrap_or(c.name()).to_string()))
+}
+
+// Every suite this OpenSSL build can offer, TLS 1.3 ones included.
+fn local_suites() -> Result<Vec<Suite>> {
+    let mut builder = legacy_connector_builder()?;
+    builder.set_cipher_list("ALL:COMPLEMENTOFALL@SECLEVEL=0")?;
+    builder.set_ciphersuites(TLS13_SUITES)?;
+    let ssl = Ssl::new(&builder.build().into_context())?;
+    let suites = ffi::ciphers(&ssl)
+        .into_iter()
+        .map(|cipher| {
+            let version = cipher.version();
+            Suite {
+                name: cipher.name().to_string(),
+                standard_name: cipher.standard_name().unwrap_or(cipher.name()).to_string(),
+                tls13: version == "TLSv1.3",
+                // SSLv3 and TLSv1.0 suites are usable from TLS 1.0 on
+                min_version: VERSIONS.iter().position(|(_, label)| *label == version).unwrap_or(0),
+            }
+        })
+        .collect();
+    Ok(suites)
+}
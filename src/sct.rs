@@ -0,0 +1,351 @@
+use anyhow::{bail, Context, Result};
+use crate::exit::Failure;
+use crate::extensions::embedded_sct_list;
+use crate::ffi;
+use crate::report::{Issue, SctCheck, SctInfo, SctSource};
+use crate::util::{der_encode, der_next, format_asn1_time};
+use openssl::asn1::Asn1Time;
+use openssl::base64;
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::{PKey, Public};
+use openssl::sign::Verifier;
+use openssl::ssl::{ConnectConfiguration, SslRef};
+use openssl::x509::X509Ref;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+// Certificate Transparency (RFC 6962): SCTs embedded in the leaf, sent in the
+// signed_certificate_timestamp TLS extension or in the stapled OCSP response,
+// verified against a log list.
+
+/// OID 1.3.6.1.4.1.11129.2.4.2 (embedded SCT list), DER content bytes.
+const SCT_LIST_OID_DER: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x02];
+
+/// A log from the `--ct-logs` list; its ID is the SHA-256 of its key.
+pub struct CtLog {
+    name: String,
+    id: Vec<u8>,
+    key: PKey<Public>,
+}
+
+// Subset of the v3 log list schema (Chrome's log_list.json).
+#[derive(Deserialize)]
+struct LogList {
+    operators: Vec<LogOperator>,
+}
+
+#[derive(Deserialize)]
+struct LogOperator {
+    #[serde(default)]
+    logs: Vec<LogEntry>,
+    #[serde(default)]
+    tiled_logs: Vec<LogEntry>,
+}
+
+#[derive(Deserialize)]
+struct LogEntry {
+    description: String,
+    /// Base64 DER SubjectPublicKeyInfo.
+    key: String,
+}
+
+/// Load the logs of a v3 log list JSON file.
+pub fn load_log_list(path: &Path) -> Result<Vec<CtLog>> {
+    let data = std::fs::read(path)
+        .with_context(|| Failure::Input(format!("failed to read CT log list {}", path.display())))?;
+    let list: LogList = serde_json::from_slice(&data)
+        .with_context(|| Failure::Input(format!("failed to parse CT log list {}", path.display())))?;
+    let mut logs = Vec::new();
+    for entry in list.operators.into_iter().flat_map(|op| op.logs.into_iter().chain(op.tiled_logs)) {
+        let der = base64::decode_block(&entry.key)
+            .ok()
+            .filter(|der| PKey::public_key_from_der(der).is_ok())
+            .with_context(|| Failure::Input(format!("invalid key for CT log {}", entry.description)))?;
+        logs.push(CtLog {
+            id: hash(MessageDigest::sha256(), &der)?.to_vec(),
+            key: PKey::public_key_from_der(&der)?,
+            name: entry.description,
+        });
+    }
+    Ok(logs)
+}
+
+/// An SCT as received, TLS-encoded.
+pub struct RawSct {
+    pub source: SctSource,
+    pub data: Vec<u8>,
+}
+
+/// Send the signed_certificate_timestamp extension in the ClientHello.
+/// Validation is left permissive: we report, OpenSSL must not abort.
+pub fn request_scts(config: &mut ConnectConfiguration) -> Result<()> {
+    Ok(ffi::enable_ct_permissive(config)?)
+}
+
+/// SCTs OpenSSL collected from the TLS extension and the stapled OCSP
+/// response; embedded ones are read from the leaf by `check_scts`.
+pub fn peer_scts(ssl: &SslRef) -> Vec<RawSct> {
+    ffi::peer_scts(ssl)
+        .into_iter()
+        .filter_map(|(source, data)| {
+            let source = match source {
+                ffi::SCT_SOURCE_TLS_EXTENSION => SctSource::TlsExtension,
+                ffi::SCT_SOURCE_OCSP_STAPLED_RESPONSE => SctSource::Ocsp,
+                _ => return None,
+            };
+            Some(RawSct { source, data })
+        })
+        .collect()
+}
+
+/// Decode and verify the leaf's embedded SCTs plus `delivered` ones, and
+/// evaluate the count against the CT policy.
+pub fn check_scts(seq: &[&X509Ref], delivered: &[RawSct], logs: &[CtLog]) -> SctCheck {
+    let mut check = SctCheck::default();
+    let Some(leaf) = seq.first() else { return check };
+
+    let mut raw: Vec<(SctSource, &[u8])> = Vec::new();
+    let embedded = embedded_sct_list(leaf);
+    if let Some(list) = &embedded {
+        match split_sct_list(list) {
+            Ok(items) => raw.extend(items.into_iter().map(|i| (SctSource::Embedded, i))),
+            Err(e) => check.errors.push(format!("embedded SCT list: {:#}", e)),
+        }
+    }
+    raw.extend(delivered.iter().map(|r| (r.source, r.data.as_slice())));
+
+    for (source, data) in raw {
+        match Sct::parse(data) {
+            Ok(sct) => check.scts.push(inspect(&sct, source, leaf, seq.get(1).copied(), logs)),
+            Err(e) => check.errors.push(format!("{} SCT: {:#}", source_label(source), e)),
+        }
+    }
+
+    let lifetime_days = leaf.not_before().diff(leaf.not_after()).map(|d| d.days).unwrap_or(0);
+    check.required_embedded = if lifetime_days <= 180 { 2 } else { 3 };
+    let embedded_logs = distinct_logs(&check.scts, |s| s == SctSource::Embedded);
+    let delivered_logs = distinct_logs(&check.scts, |s| s != SctSource::Embedded);
+    check.compliant = embedded_logs >= check.required_embedded || delivered_logs >= 2;
+    check
+}
+
+/// Issue lines: too few SCTs, bad signatures and undecodable entries.
+pub fn sct_issues(check: &SctCheck) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if !check.compliant {
+        issues.push(Issue::warning(format!(
+            "not enough SCTs: {} embedded ({} required), {} delivered in the handshake (2 required)",
+            distinct_logs(&check.scts, |s| s == SctSource::Embedded),
+            check.required_embedded,
+            distinct_logs(&check.scts, |s| s != SctSource::Embedded)
+        )));
+    }
+    for sct in &check.scts {
+        let log = sct.log.as_deref().unwrap_or(&sct.log_id);
+        if sct.verified == Some(false) {
+            issues.push(Issue::warning(format!("{} SCT from {} does not verify", source_label(sct.source), log)));
+        }
+        if let Some(err) = &sct.error {
+            issues.push(Issue::warning(format!("{} SCT from {} could not be checked: {}", source_label(sct.source), log, err)));
+        }
+    }
+    issues.extend(check.errors.iter().map(|e| Issue::warning(format!("malformed {}", e))));
+    issues
+}
+
+pub fn source_label(source: SctSource) -> &'static str {
+    match source {
+        SctSource::Embedded => "embedded",
+        SctSource::TlsExtension => "TLS extension",
+        SctSource::Ocsp => "OCSP",
+    }
+}
+
+// Logs with at least one SCT from `source` that is not known to be bad.
+fn distinct_logs(scts: &[SctInfo], source: impl Fn(SctSource) -> bool) -> usize {
+    scts.iter()
+        .filter(|s| source(s.source) && s.verified != Some(false))
+        .map(|s| s.log_id.as_str())
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+fn inspect(sct: &Sct, source: SctSource, leaf: &X509Ref, issuer: Option<&X509Ref>, logs: &[CtLog]) -> SctInfo {
+    let log = logs.iter().find(|l| l.id == sct.log_id);
+    let mut info = SctInfo {
+        source,
+        log_id: base64::encode_block(&sct.log_id),
+        log: log.map(|l| l.name.clone()),
+        timestamp: Asn1Time::from_unix((sct.timestamp / 1000) as _)
+            .map(|t| format_asn1_time(&t))
+            .unwrap_or_else(|_| sct.timestamp.to_string()),
+        signature_algorithm: format!("{}-{}", signature_name(sct.sig_alg), hash_name(sct.hash_alg)),
+        verified: None,
+        error: None,
+    };
+    if let Some(log) = log {
+        match verify(sct, source, leaf, issuer, &log.key) {
+            Ok(ok) => info.verified = Some(ok),
+            Err(e) => info.error = Some(format!("{:#}", e)),
+        }
+    }
+    info
+}
+
+fn verify(sct: &Sct, source: SctSource, leaf: &X509Ref, issuer: Option<&X509Ref>, key: &PKey<Public>) -> Result<bool> {
+    let digest = match sct.hash_alg {
+        4 => MessageDigest::sha256(),
+        5 => MessageDigest::sha384(),
+        6 => MessageDigest::sha512(),
+        other => bail!("unsupported hash algorithm {}", hash_name(other)),
+    };
+    // digitally-signed struct of RFC 6962 section 3.2
+    let mut signed = vec![sct.version, 0];
+    signed.extend(sct.timestamp.to_be_bytes());
+    if source == SctSource::Embedded {
+        let issuer = issuer.context("issuer of the leaf is not in the chain")?;
+        signed.extend(1u16.to_be_bytes());
+        signed.extend(hash(MessageDigest::sha256(), &issuer.public_key()?.public_key_to_der()?)?.iter());
+        push_u24(&mut signed, &precert_tbs(leaf)?)?;
+    } else {
+        signed.extend(0u16.to_be_bytes());
+        push_u24(&mut signed, &leaf.to_der()?)?;
+    }
+    signed.extend((sct.extensions.len() as u16).to_be_bytes());
+    signed.extend(&sct.extensions);
+
+    let mut verifier = Verifier::new(digest, key)?;
+    verifier.update(&signed)?;
+    Ok(verifier.verify(&sct.signature).unwrap_or(false))
+}
+
+/// Decoded SignedCertificateTimestamp.
+struct Sct {
+    version: u8,
+    log_id: Vec<u8>,
+    /// Milliseconds since the epoch.
+    timestamp: u64,
+    extensions: Vec<u8>,
+    hash_alg: u8,
+    sig_alg: u8,
+    signature: Vec<u8>,
+}
+
+impl Sct {
+    fn parse(data: &[u8]) -> Result<Sct> {
+        let mut r = Reader(data);
+        let version = r.u8()?;
+        if version != 0 {
+            bail!("unsupported SCT version {}", version);
+        }
+        let sct = Sct {
+            version,
+            log_id: r.take(32)?.to_vec(),
+            timestamp: u64::from_be_bytes(r.take(8)?.try_into()?),
+            extensions: r.vec16()?.to_vec(),
+            hash_alg: r.u8()?,
+            sig_alg: r.u8()?,
+            signature: r.vec16()?.to_vec(),
+        };
+        if !r.0.is_empty() {
+            bail!("trailing data after SCT");
+        }
+        Ok(sct)
+    }
+}
+
+// SignedCertificateTimestampList: u16 total length, then u16-prefixed SCTs.
+fn split_sct_list(data: &[u8]) -> Result<Vec<&[u8]>> {
+    let mut outer = Reader(data);
+    let mut list = Reader(outer.vec16()?);
+    let mut out = Vec::new();
+    while !list.0.is_empty() {
+        out.push(list.vec16()?);
+    }
+    Ok(out)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.0.len() < n {
+            bail!("truncated");
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn vec16(&mut self) -> Result<&'a [u8]> {
+        let len = u16::from_be_bytes(self.take(2)?.try_into()?);
+        self.take(len as usize)
+    }
+}
+
+fn push_u24(out: &mut Vec<u8>, data: &[u8]) -> Result<()> {
+    if data.len() >= 1 << 24 {
+        bail!("certificate too large");
+    }
+    out.extend(&(data.len() as u32).to_be_bytes()[1..]);
+    out.extend(data);
+    Ok(())
+}
+
+// TBSCertificate of the leaf without the SCT list extension, which is what
+// the log signed for the precertificate (RFC 6962 section 3.2).
+fn precert_tbs(cert: &X509Ref) -> Result<Vec<u8>> {
+    let der = cert.to_der()?;
+    let (_, cert_body, _) = der_next(&der)?;
+    let (_, tbs_body, _) = der_next(cert_body)?;
+    let mut fields = Vec::new();
+    let mut rest = tbs_body;
+    while !rest.is_empty() {
+        let (tag, content, next) = der_next(rest)?;
+        if tag == 0xa3 {
+            let (_, mut exts, _) = der_next(content)?;
+            let mut kept = Vec::new();
+            while !exts.is_empty() {
+                let (_, ext_body, after) = der_next(exts)?;
+                let (_, oid, _) = der_next(ext_body)?;
+                if oid != SCT_LIST_OID_DER {
+                    kept.extend(&exts[..exts.len() - after.len()]);
+                }
+                exts = after;
+            }
+            fields.extend(der_encode(0xa3, &der_encode(0x30, &kept)));
+        } else {
+            fields.extend(&rest[..rest.len() - next.len()]);
+        }
+        rest = next;
+    }
+    Ok(der_encode(0x30, &fields))
+}
+
+fn hash_name(id: u8) -> String {
+    match id {
+        0 => "none".to_string(),
+        1 => "md5".to_string(),
+        2 => "sha1".to_string(),
+        3 => "sha224".to_string(),
+        4 => "sha256".to_string(),
+        5 => "sha384".to_string(),
+        6 => "sha512".to_string(),
+        other => format!("hash{}", other),
+    }
+}
+
+fn signature_name(id: u8) -> String {
+    match id {
+        0 => "anonymous".to_string(),
+        1 => "rsa".to_string(),
+        2 => "dsa".to_string(),
+        3 => "ecdsa".to_string(),
+        other => format!("sig{}", other),
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::{bail, Context, Result};
+use crate::cli::StartTls;
+use crate::exit::Failure;
+use crate::net::is_timeout;
+use crate::util::der_next;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+// Plaintext upgrade dialogues run on the TCP stream before the handshake.
+// Replies are read a byte at a time so nothing of the server's first TLS
+// record is consumed.
+
+impl StartTls {
+    /// Standard port of the protocol's plaintext listener.
+    pub fn default_port(self) -> u16 {
+        match self {
+            StartTls::Smtp => 25,
+            StartTls::Imap => 143,
+            StartTls::Pop3 => 110,
+            StartTls::Ftp => 21,
+            StartTls::Ldap => 389,
+            StartTls::Xmpp => 5222,
+            StartTls::Postgres => 5432,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            StartTls::Smtp => "SMTP",
+            StartTls::Imap => "IMAP",
+            StartTls::Pop3 => "POP3",
+            StartTls::Ftp => "FTP",
+            StartTls::Ldap => "LDAP",
+            StartTls::Xmpp => "XMPP",
+            StartTls::Postgres => "PostgreSQL",
+        }
+    }
+}
+
+/// Ask the server to switch `stream` to TLS; on success the next bytes on
+/// the wire are the handshake. `host` is the XMPP domain.
+pub fn upgrade(stream: &mut TcpStream, proto: StartTls, host: &str) -> Result<()> {
+    let result = match proto {
+        StartTls::Smtp => smtp(stream),
+        StartTls::Imap => imap(stream),
+        StartTls::Pop3 => pop3(stream),
+        StartTls::Ftp => ftp(stream),
+        StartTls::Ldap => ldap(stream),
+        StartTls::Xmpp => xmpp(stream, host),
+        StartTls::Postgres => postgres(stream),
+    };
+    result.map_err(|e| {
+        let msg = format!("{} STARTTLS failed: {:#}", proto.label(), e);
+        match e.downcast_ref::<io::Error>() {
+            Some(io) if is_timeout(io.kind()) => Failure::Timeout(msg).into(),
+            _ => Failure::Connection(msg).into(),
+        }
+    })
+}
+
+fn smtp(s: &mut TcpStream) -> Result<()> {
+    expect_reply(s, "220")?;
+    s.write_all(b"EHLO tls-doctor\r\n")?;
+    expect_reply(s, "250")?;
+    s.write_all(b"STARTTLS\r\n")?;
+    expect_reply(s, "220")?;
+    Ok(())
+}
+
+fn ftp(s: &mut TcpStream) -> Result<()> {
+    expect_reply(s, "220")?;
+    s.write_all(b"AUTH TLS\r\n")?;
+    expect_reply(s, "234")?;
+    Ok(())
+}
+
+fn imap(s: &mut TcpStream) -> Result<()> {
+    let greeting = read_line(s)?;
+    if !greeting.starts_with("* OK") {
+        bail!("unexpected greeting: {}", greeting);
+    }
+    s.write_all(b"a001 STARTTLS\r\n")?;
+    // Untagged lines may come first
+    loop {
+        let line = read_line(s)?;
+        if let Some(status) = line.strip_prefix("a001 ") {
+            if status.starts_with("OK") {
+                return Ok(());
+            }
+            bail!("server answered: {}", line);
+        }
+    }
+}
+
+fn pop3(s: &mut TcpStream) -> Result<()> {
+    let greeting = read_line(s)?;
+    if !greeting.starts_with("+OK") {
+        bail!("unexpected greeting: {}", greeting);
+    }
+    s.write_all(b"STLS\r\n")?;
+    let line = read_line(s)?;
+    if !line.starts_with("+OK") {
+        bail!("server answered: {}", line);
+    }
+    Ok(())
+}
+
+/// OID of the StartTLS extended operation (RFC 4511 section 4.14).
+const LDAP_STARTTLS_OID: &[u8] = b"1.3.6.1.4.1.1466.20037";
+
+fn ldap(s: &mut TcpStream) -> Result<()> {
+    // LDAPMessage { messageID 1, ExtendedRequest [APPLICATION 23] { requestName [0] } }
+    let name = [&[0x80, LDAP_STARTTLS_OID.len() as u8][..], LDAP_STARTTLS_OID].concat();
+    let op = [&[0x77, name.len() as u8][..], &name].concat();
+    let body = [&[0x02, 0x01, 0x01][..], &op].concat();
+    s.write_all(&[&[0x30, body.len() as u8][..], &body].concat())?;
+
+    let msg = read_ber(s)?;
+    // messageID, then ExtendedResponse [APPLICATION 24] starting with resultCode
+    let (_, _, rest) = der_next(&msg).context("malformed LDAP response")?;
+    let (tag, op, _) = der_next(rest).context("malformed LDAP response")?;
+    if tag != 0x78 {
+        bail!("unexpected LDAP response tag {:#04x}", tag);
+    }
+    match op {
+        [0x0a, 0x01, 0, ..] => Ok(()),
+        [0x0a, 0x01, code, ..] => bail!("server answered resultCode {}", code),
+        _ => bail!("malformed LDAP response"),
+    }
+}
+
+fn xmpp(s: &mut TcpStream, host: &str) -> Result<()> {
+    write!(
+        s,
+        "<?xml version='1.0'?><stream:stream xmlns='jabber:client' \
+         xmlns:stream='http://etherx.jabber.org/streams' to='{}' version='1.0'>",
+        host
+    )?;
+    let features = read_until(s, &["</stream:features>", "</stream:stream>"])?;
+    if !features.contains("urn:ietf:params:xml:ns:xmpp-tls") {
+        bail!("server does not offer starttls");
+    }
+    s.write_all(b"<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>")?;
+    let reply = read_until(s, &["/>", "</proceed>", "</failure>"])?;
+    if !reply.contains("<proceed") {
+        bail!("server answered: {}", reply.trim());
+    }
+    Ok(())
+}
+
+fn postgres(s: &mut TcpStream) -> Result<()> {
+    // SSLRequest: length 8, request code 80877103
+    s.write_all(&[0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f])?;
+    let mut answer = [0u8; 1];
+    s.read_exact(&mut answer)?;
+    match answer[0] {
+        b'S' => Ok(()),
+        b'N' => bail!("server does not accept SSL"),
+        other => bail!("unexpected answer {:#04x}", other),
+    }
+}
+
+// Read a possibly multi-line reply (`250-...` continued, `250 ...` last) and
+// check its code.
+fn expect_reply(s: &mut TcpStream, code: &str) -> Result<()> {
+    loop {
+        let line = read_line(s)?;
+        if !line.starts_with(code) {
+            bail!("server answered: {}", line);
+        }
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
+
+fn read_line(s: &mut TcpStream) -> Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    while line.last() != Some(&b'\n') {
+        if s.read(&mut byte)? == 0 {
+            bail!("connection closed by server");
+        }
+        line.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+}
+
+fn read_until(s: &mut TcpStream, ends: &[&str]) -> Result<String> {
+    let mut text = Vec::new();
+    let mut byte = [0u8; 1];
+    while !ends.iter().any(|e| text.ends_with(e.as_bytes())) {
+        if s.read(&mut byte)? == 0 {
+            bail!("connection closed by server");
+        }
+        text.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&text).into_owned())
+}
+
+// One LDAPMessage off the wire, returned as its content.
+fn read_ber(s: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut head = [0u8; 2];
+    s.read_exact(&mut head)?;
+    if head[0] != 0x30 {
+        bail!("unexpected tag {:#04x}", head[0]);
+    }
+    let len = if head[1] < 0x80 {
+        head[1] as usize
+    } else {
+        let mut bytes = vec![0u8; (head[1] & 0x7f) as usize];
+        if bytes.is_empty() || bytes.len() > 4 {
+            bail!("unsupported BER length");
+        }
+        s.read_exact(&mut bytes)?;
+        bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize)
+    };
+    let mut content = vec![0u8; len];
+    s.read_exact(&mut content)?;
+    Ok(content)
+}
@@ -1,9 +1,9 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use openssl::hash::MessageDigest;
 use openssl::nid::Nid;
 use openssl::pkey::Id as KeyId;
 use openssl::x509::{X509NameEntries, X509Ref};
-use openssl::asn1::Asn1TimeRef;
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
 
 /// Extract a subset of X.509 name attributes and map them to human labels
 /// in a consistent order for display.
@@ -125,10 +125,15 @@ pub fn format_name_human(entries: X509NameEntries<'_>) -> String {
 /// Return a colon-separated SHA-256 fingerprint (uppercase hex).
 pub fn fingerprint_sha256(cert: &X509Ref) -> Result<String> {
     let d = cert.digest(MessageDigest::sha256())?;
-    Ok(d.iter()
+    Ok(hex_colon(&d))
+}
+
+/// Colon-separated uppercase hex, as used for fingerprints and key identifiers.
+pub fn hex_colon(bytes: &[u8]) -> String {
+    bytes.iter()
         .map(|b| format!("{:02X}", b))
         .collect::<Vec<_>>()
-        .join(":"))
+        .join(":")
 }
 
 /// Format ASN1 time to a human-readable string.
@@ -136,6 +141,12 @@ pub fn format_asn1_time(time: &Asn1TimeRef) -> String {
     time.to_string()
 }
 
+/// Whole days from now until `time`; negative once `time` has passed.
+pub fn days_from_now(time: &Asn1TimeRef) -> Option<i64> {
+    let now = Asn1Time::days_from_now(0).ok()?;
+    now.diff(time).ok().map(|d| d.days as i64)
+}
+
 /// Try to get the named curve for EC public keys (short or long name).
 pub fn ec_curve_name(pkey: &openssl::pkey::PKeyRef<openssl::pkey::Public>) -> Option<String> {
     if pkey.id() != KeyId::EC { return None; }
@@ -173,6 +184,11 @@ pub fn subject_cn(cert: &X509Ref) -> Option<String> {
     None
 }
 
+/// Short `CN=...` label for issue messages.
+pub fn cn_label(cert: &X509Ref) -> String {
+    subject_cn(cert).map(|cn| format!("CN={}", cn)).unwrap_or_else(|| "<unknown>".to_string())
+}
+
 /// Convenience: extract Issuer Common Name (CN) if present.
 pub fn issuer_cn(cert: &X509Ref) -> Option<String> {
     for e in cert.issuer_name().entries() {
@@ -182,3 +198,41 @@ pub fn issuer_cn(cert: &X509Ref) -> Option<String> {
     }
     None
 }
+
+/// Split one DER TLV off `data`: (tag, content, remainder).
+pub fn der_next(data: &[u8]) -> Result<(u8, &[u8], &[u8])> {
+    let [tag, first, rest @ ..] = data else {
+        bail!("truncated DER");
+    };
+    let (len, rest) = if *first < 0x80 {
+        (*first as usize, rest)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 {
+            bail!("unsupported DER length");
+        }
+        let Some((bytes, rest)) = rest.split_at_checked(n) else {
+            bail!("truncated DER");
+        };
+        (bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize), rest)
+    };
+    let Some((content, rest)) = rest.split_at_checked(len) else {
+        bail!("truncated DER");
+    };
+    Ok((*tag, content, rest))
+}
+
+/// One DER TLV: `tag`, definite length, `content`.
+pub fn der_encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len.to_be_bytes().iter().copied().skip_while(|b| *b == 0).collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend(content);
+    out
+}
@@ -1,29 +1,221 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use openssl::ssl::{SslConnector, SslConnectorBuilder, SslContext, SslFiletype};
 use openssl::stack::Stack;
-use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::store::{X509Lookup, X509Store, X509StoreBuilder, X509StoreRef};
+use openssl::asn1::Asn1Time;
 use openssl::x509::{X509, X509Ref, X509StoreContext};
-use crate::util::format_name_human;
-use crate::print::print_bold;
-use std::io::Write;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use crate::exit::Failure;
+use crate::ffi;
+use crate::report::{AnchorSource, ExpiryStatus, HostnameCheck, Issue, TrustAnchor, Verdict};
+use crate::util::{cn_label, days_from_now, fingerprint_sha256, format_asn1_time, format_name_human, issuer_cn};
+use std::cell::OnceCell;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 
-/// Verify `leaf` against the system trust store with optional intermediates `chain`.
-/// Returns Ok(Ok(())) on success, Ok(Err(msg)) for a verify failure with human context,
+/// Where trust anchors come from when verifying a chain.
+#[derive(Debug, Clone)]
+pub struct TrustOptions {
+    /// PEM bundle of additional roots.
+    pub ca_file: Option<PathBuf>,
+    /// OpenSSL hashed directory (`c_rehash` layout) of additional roots.
+    pub ca_dir: Option<PathBuf>,
+    /// Include OpenSSL's default CA locations (system trust store).
+    pub system: bool,
+}
+
+impl Default for TrustOptions {
+    fn default() -> Self {
+        TrustOptions { ca_file: None, ca_dir: None, system: true }
+    }
+}
+
+/// Verification store built from `TrustOptions`. Keeps the user-supplied
+/// anchors around to attribute the anchor a chain terminated at.
+pub struct TrustStore {
+    anchors: Anchors,
+    ca_file_anchors: Vec<X509>,
+    ca_dir: Option<PathBuf>,
+    /// `--ca-dir` and the system's hashed directories, if used.
+    hash_dirs: Vec<PathBuf>,
+}
+
+// The store itself, or the TLS context it was handed to (see `build_connector`).
+enum Anchors {
+    Store(X509Store),
+    Context(SslContext),
+}
+
+impl TrustStore {
+    pub fn load(opts: &TrustOptions) -> Result<Self> {
+        let ca_file_anchors = match &opts.ca_file {
+            Some(path) => {
+                let data = std::fs::read(path)
+                    .with_context(|| Failure::Input(format!("failed to read CA file {}", path.display())))?;
+                let certs = X509::stack_from_pem(&data)
+                    .with_context(|| Failure::Input(format!("failed to parse PEM certificates from {}", path.display())))?;
+                if certs.is_empty() {
+                    return Err(Failure::Input(format!("no certificates found in {}", path.display())).into());
+                }
+                certs
+            }
+            None => Vec::new(),
+        };
+        if let Some(dir) = &opts.ca_dir {
+            if !dir.is_dir() {
+                return Err(Failure::Input(format!("CA directory {} does not exist", dir.display())).into());
+            }
+        }
+
+        let mut builder = X509StoreBuilder::new()?;
+        if opts.system {
+            // Use OpenSSL's default CA locations (system trust store)
+            builder.set_default_paths()?;
+        }
+        for c in &ca_file_anchors {
+            builder.add_cert(c.clone())?;
+        }
+        if let Some(dir) = &opts.ca_dir {
+            let lookup = builder.add_lookup(X509Lookup::hash_dir())?;
+            lookup.add_dir(&dir.to_string_lossy(), SslFiletype::PEM)?;
+        }
+
+        let mut hash_dirs: Vec<PathBuf> = opts.ca_dir.iter().cloned().collect();
+        if opts.system {
+            hash_dirs.extend(ffi::default_cert_dirs());
+        }
+        Ok(TrustStore {
+            anchors: Anchors::Store(builder.build()),
+            ca_file_anchors,
+            ca_dir: opts.ca_dir.clone(),
+            hash_dirs,
+        })
+    }
+
+    pub fn store(&self) -> &X509StoreRef {
+        match &self.anchors {
+            Anchors::Store(store) => store,
+            Anchors::Context(context) => context.cert_store(),
+        }
+    }
+
+    /// Verify the handshakes of `builder`'s connector against this store.
+    /// The store moves into the connector and the returned `TrustStore` reads
+    /// it from there, so the anchors are loaded once for both.
+    pub fn build_connector(self, mut builder: SslConnectorBuilder) -> Result<(SslConnector, TrustStore)> {
+        let Anchors::Store(store) = self.anchors else {
+            anyhow::bail!("trust store is already used by a connector");
+        };
+        builder.set_cert_store(store);
+        let connector = builder.build();
+        let anchors = Anchors::Context(connector.context().to_owned());
+        Ok((connector, TrustStore { anchors, ..self }))
+    }
+
+    /// Certificates of the store named like `cert`'s issuer. Hashed
+    /// directories are only read on lookup, so their `<hash>.N` files for
+    /// that name are read here.
+    pub fn issuer_candidates(&self, cert: &X509Ref) -> Vec<X509> {
+        let name = cert.issuer_name().to_der().unwrap_or_default();
+        let mut found: Vec<X509> = Vec::new();
+        let loaded = self.store().all_certificates().into_iter();
+        let hashed = self.hash_dirs.iter().flat_map(|dir| {
+            (0..)
+                .map_while(|n| std::fs::read(dir.join(format!("{:08x}.{}", cert.issuer_name_hash(), n))).ok())
+                .filter_map(|data| X509::from_pem(&data).or_else(|_| X509::from_der(&data)).ok())
+        });
+        for c in loaded.chain(hashed) {
+            let named = c.subject_name().to_der().is_ok_and(|d| d == name);
+            if named && !found.iter().any(|f| f.to_der().ok() == c.to_der().ok()) {
+                found.push(c);
+            }
+        }
+        found
+    }
+
+    fn anchor_source(&self, anchor: &X509Ref) -> AnchorSource {
+        let fp = fingerprint_sha256(anchor).unwrap_or_default();
+        let matches = |c: &X509| fingerprint_sha256(c).map(|f| f == fp).unwrap_or(false);
+        if self.ca_file_anchors.iter().any(matches) {
+            return AnchorSource::CaFile;
+        }
+        if let Some(dir) = &self.ca_dir {
+            if dir_certs(dir).iter().any(matches) {
+                return AnchorSource::CaDir;
+            }
+        }
+        AnchorSource::System
+    }
+}
+
+// Best-effort read of every certificate in a hashed directory (PEM or DER files).
+fn dir_certs(dir: &Path) -> Vec<X509> {
+    let mut out = Vec::new();
+    let Ok(entries) = std::fs::read_dir(dir) else { return out };
+    for entry in entries.flatten() {
+        let Ok(data) = std::fs::read(entry.path()) else { continue };
+        match X509::stack_from_pem(&data) {
+            Ok(stack) if !stack.is_empty() => out.extend(stack),
+            _ => out.extend(X509::from_der(&data).ok()),
+        }
+    }
+    out
+}
+
+/// A `TrustStore` loaded on first use, for commands that may not need one.
+pub struct LazyTrustStore {
+    opts: TrustOptions,
+    store: OnceCell<TrustStore>,
+}
+
+impl LazyTrustStore {
+    pub fn new(opts: TrustOptions) -> Self {
+        LazyTrustStore { opts, store: OnceCell::new() }
+    }
+
+    pub fn get(&self) -> Result<&TrustStore> {
+        if let Some(store) = self.store.get() {
+            return Ok(store);
+        }
+        let store = TrustStore::load(&self.opts)?;
+        Ok(self.store.get_or_init(|| store))
+    }
+}
+
+/// Verify `leaf` against `trust` with optional intermediates `chain`.
+/// Returns Ok(Ok(anchor)) on success with the anchor the path terminated at,
+/// Ok(Err(msg)) for a verify failure with human context,
 /// or Err(e) for unexpected OpenSSL errors while setting up verification.
-pub fn validate_chain(leaf: &X509Ref, chain: &[&X509Ref]) -> Result<Result<(), String>> {
-    let mut builder = X509StoreBuilder::new()?;
-    // Use OpenSSL's default CA locations (system trust store)
-    builder.set_default_paths()?;
-    let store = builder.build();
+pub fn validate_chain(leaf: &X509Ref, chain: &[&X509Ref], trust: &TrustStore) -> Result<Result<TrustAnchor, String>> {
+    let path = match verified_chain(leaf, chain, trust)? {
+        Ok(path) => path,
+        Err(msg) => return Ok(Err(msg)),
+    };
+    let anchor = path.last().ok_or_else(|| anyhow::anyhow!("verified chain is empty"))?;
+    let subject = format_name_human(anchor.subject_name().entries());
+    Ok(Ok(TrustAnchor {
+        subject: if subject.is_empty() { "<unknown subject>".to_string() } else { subject },
+        fingerprint_sha256: fingerprint_sha256(anchor)?,
+        source: trust.anchor_source(anchor),
+    }))
+}
 
+/// Same verification as `validate_chain`, returning the whole path OpenSSL
+/// built (leaf first, anchor last) instead of the anchor summary.
+pub fn verified_chain(leaf: &X509Ref, chain: &[&X509Ref], trust: &TrustStore) -> Result<Result<Vec<X509>, String>> {
     let mut stack: Stack<X509> = Stack::new()?;
     for c in chain { stack.push((*c).to_owned())?; }
 
     let mut ctx = X509StoreContext::new()?;
     // Run the standard path validation. The closure is invoked by OpenSSL.
-    let ok = ctx.init(&store, &leaf.to_owned(), &stack, |c| c.verify_cert());
+    // The verified chain only lives inside the closure, so copy it there.
+    let mut path: Vec<X509> = Vec::new();
+    let ok = ctx.init(trust.store(), leaf, &stack, |c| {
+        let ok = c.verify_cert()?;
+        path = c.chain().map(|ch| ch.iter().map(|cert| cert.to_owned()).collect()).unwrap_or_default();
+        Ok(ok)
+    });
     match ok {
-        Ok(true) => Ok(Ok(())),
+        Ok(true) => Ok(Ok(path)),
         Ok(false) => {
             let err = ctx.error();
             let depth = ctx.error_depth();
@@ -38,29 +230,195 @@ pub fn validate_chain(leaf: &X509Ref, chain: &[&X509Ref]) -> Result<Result<(), S
     }
 }
 
-pub fn validate_and_report(seq: &[&X509Ref], _unused: &[&X509Ref]) -> Result<()> {
-    let mut stdout = StandardStream::stdout(ColorChoice::Auto);
-    if let Some(leaf) = seq.first() {
-        match validate_chain(leaf, &seq[1..]) {
-            Ok(Ok(())) => {
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
-                write!(&mut stdout, "✅ the chain is valid")?;
-                stdout.reset()?;
-                writeln!(&mut stdout)?;
+/// Evaluate bundle consistency: unrelated certificates, a missing issuer at the
+/// top of the ordered chain, or a root whose signature does not verify itself.
+/// Extra certificates and a missing top issuer are warnings: trust-store
+/// validation decides whether the chain itself is usable.
+pub fn bundle_issues(seq: &[&X509Ref], unused: &[&X509Ref]) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = Vec::new();
+    if !unused.is_empty() {
+        let labels = unused
+            .iter()
+            .map(|c| cn_label(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        issues.push(Issue::warning(format!("bundle contains unrelated certificate(s): {}", labels)));
+    }
+
+    if let Some(last) = seq.last() {
+        let subj = last.subject_name().to_der().unwrap_or_default();
+        let iss = last.issuer_name().to_der().unwrap_or_default();
+        if subj != iss {
+            let label = issuer_cn(last).map(|cn| format!("CN={}", cn)).unwrap_or_else(|| "<unknown>".to_string());
+            issues.push(Issue::warning(format!("chain incomplete: missing issuer {}", label)));
+        } else if let Ok(pk) = last.public_key() {
+            if last.verify(&pk).is_err() {
+                issues.push(Issue::error("root certificate signature does not verify itself"));
             }
-            Ok(Err(msg)) => {
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
-                print_bold(&mut stdout, "❌ the chain has issues:")?;
-                writeln!(&mut stdout)?;
-                writeln!(&mut stdout, "- {}", msg)?;
+        }
+    }
+    issues
+}
+
+/// Validate the ordered chain `seq` (leaf first) and fold the outcome together
+/// with previously collected `issues` into a single verdict.
+pub fn chain_verdict(seq: &[&X509Ref], mut issues: Vec<Issue>, trust: &TrustStore) -> Verdict {
+    let mut anchor = None;
+    match seq.first() {
+        Some(leaf) => match validate_chain(leaf, &seq[1..], trust) {
+            Ok(Ok(a)) => anchor = Some(a),
+            Ok(Err(msg)) => issues.push(Issue::error(msg)),
+            Err(e) => issues.push(Issue::error(format!("validation error: {}", e))),
+        },
+        None => issues.push(Issue::error("no certificates parsed")),
+    }
+    let mut verdict = Verdict::from_issues(issues);
+    verdict.trust_anchor = anchor;
+    verdict
+}
+
+/// Day thresholds below which a certificate's remaining lifetime is flagged.
+#[derive(Debug, Clone, Copy)]
+pub struct ExpiryThresholds {
+    pub warn_days: i64,
+    pub crit_days: i64,
+}
+
+impl Default for ExpiryThresholds {
+    fn default() -> Self {
+        ExpiryThresholds { warn_days: 30, crit_days: 7 }
+    }
+}
+
+/// Days remaining until Not After and the resulting classification.
+pub fn expiry_status(cert: &X509Ref, thresholds: &ExpiryThresholds) -> (Option<i64>, ExpiryStatus) {
+    let now = Asn1Time::days_from_now(0).ok();
+    let days = days_from_now(cert.not_after());
+    let status = match now.as_deref() {
+        Some(now) if cert.not_before() > now => ExpiryStatus::NotYetValid,
+        Some(now) if cert.not_after() < now => ExpiryStatus::Expired,
+        _ => match days {
+            Some(d) if d < thresholds.crit_days => ExpiryStatus::Critical,
+            Some(d) if d < thresholds.warn_days => ExpiryStatus::Warning,
+            _ => ExpiryStatus::Ok,
+        },
+    };
+    (days, status)
+}
+
+/// Lifetime issues across the ordered chain: expired, not yet valid or soon
+/// expiring certificates, and issuers that expire before the leaf.
+pub fn expiry_issues(seq: &[&X509Ref], thresholds: &ExpiryThresholds) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for (i, cert) in seq.iter().enumerate() {
+        let role = match i {
+            0 => "leaf",
+            _ if i + 1 == seq.len() && cert.subject_name().to_der().ok() == cert.issuer_name().to_der().ok() => "root",
+            _ => "intermediate",
+        };
+        let label = cn_label(cert);
+        let (days, status) = expiry_status(cert, thresholds);
+        let days = days.unwrap_or_default();
+        match status {
+            ExpiryStatus::NotYetValid => issues.push(Issue::error(format!(
+                "{} {} not yet valid (Not Before {})", role, label, format_asn1_time(cert.not_before())
+            ))),
+            ExpiryStatus::Expired => issues.push(Issue::error(format!(
+                "{} {} expired on {}", role, label, format_asn1_time(cert.not_after())
+            ))),
+            ExpiryStatus::Critical => issues.push(Issue::error(format!(
+                "{} {} expires in {} day(s) (critical threshold {})", role, label, days, thresholds.crit_days
+            ))),
+            ExpiryStatus::Warning => issues.push(Issue::warning(format!(
+                "{} {} expires in {} day(s) (warning threshold {})", role, label, days, thresholds.warn_days
+            ))),
+            ExpiryStatus::Ok => {}
+        }
+        if i > 0 && cert.not_after() < seq[0].not_after() {
+            issues.push(Issue::warning(format!(
+                "{} {} expires before leaf ({} < {})",
+                role,
+                label,
+                format_asn1_time(cert.not_after()),
+                format_asn1_time(seq[0].not_after())
+            )));
+        }
+    }
+    issues
+}
+
+/// Check that the leaf's subjectAltName covers `name` (dNSName or iPAddress).
+/// The subject CN is deliberately ignored, as browsers do.
+pub fn check_hostname(leaf: &X509Ref, name: &str) -> HostnameCheck {
+    let ip: Option<IpAddr> = name.trim_start_matches('[').trim_end_matches(']').parse().ok();
+    let mut matched_entry = None;
+    if let Some(sans) = leaf.subject_alt_names() {
+        for gn in sans.iter() {
+            let hit = match (ip, gn.dnsname(), gn.ipaddress()) {
+                (None, Some(pattern), _) => dns_name_matches(pattern, name).then(|| format!("DNS:{}", pattern)),
+                (Some(addr), _, Some(bytes)) => ip_from_bytes(bytes)
+                    .filter(|a| *a == addr)
+                    .map(|a| format!("IP:{}", a)),
+                _ => None,
+            };
+            if hit.is_some() {
+                matched_entry = hit;
+                break;
+            }
+        }
+    }
+    HostnameCheck { name: name.to_string(), matched: matched_entry.is_some(), matched_entry }
+}
+
+/// Turn a hostname check into a summary issue, listing what the leaf does cover.
+pub fn hostname_issue(leaf: &X509Ref, check: &HostnameCheck) -> Option<Issue> {
+    if check.matched {
+        return None;
+    }
+    let names = san_entries(leaf);
+    let covered = if names.is_empty() { "no subjectAltName entries".to_string() } else { names.join(", ") };
+    Some(Issue::error(format!("hostname mismatch: leaf does not cover {} ({})", check.name, covered)))
+}
+
+/// Printable SAN dNSName/iPAddress entries, in certificate order.
+pub fn san_entries(cert: &X509Ref) -> Vec<String> {
+    let Some(sans) = cert.subject_alt_names() else { return Vec::new() };
+    sans.iter()
+        .filter_map(|gn| {
+            gn.dnsname()
+                .map(|d| format!("DNS:{}", d))
+                .or_else(|| gn.ipaddress().and_then(ip_from_bytes).map(|a| format!("IP:{}", a)))
+        })
+        .collect()
+}
+
+fn ip_from_bytes(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => <[u8; 4]>::try_from(bytes).ok().map(IpAddr::from),
+        16 => <[u8; 16]>::try_from(bytes).ok().map(IpAddr::from),
+        _ => None,
+    }
+}
+
+// RFC 6125 section 6.4: case-insensitive comparison; a wildcard is only honored
+// as the complete left-most label, matches exactly one label, and needs at
+// least two labels after it (no `*.com`).
+fn dns_name_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    if pattern.is_empty() || name.is_empty() {
+        return false;
+    }
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => {
+            if suffix.contains('*') || suffix.split('.').count() < 2 {
+                return false;
             }
-            Err(e) => {
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
-                print_bold(&mut stdout, "❌ the chain has issues:")?;
-                writeln!(&mut stdout)?;
-                writeln!(&mut stdout, "- validation error: {}", e)?;
+            match name.split_once('.') {
+                Some((first, rest)) => !first.is_empty() && rest == suffix,
+                None => false,
             }
         }
+        None => !pattern.contains('*') && pattern == name,
     }
-    Ok(())
 }